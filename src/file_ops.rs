@@ -0,0 +1,235 @@
+use std::{error::Error, ffi::OsStr, fmt, fs, io, path::{Path, PathBuf}};
+
+use crate::{DirectoryComboBox, DirectoryComboBoxLabels, display_name, trash};
+
+/// Why the combo box refused or couldn't finish a file operation.
+///
+/// It is carried inside the `io::Error`s the operations return, so the popups can describe it with
+/// the labels rather than the English message.
+#[derive(Debug)]
+pub(crate) enum FileOperationError {
+    InvalidName(String),
+    AlreadyExists(String),
+    /// A root, or a path outside the tree or not scanned from disk, which mustn't be changed.
+    Refused(String),
+    /// The entry was created but couldn't be read back into the tree.
+    ScanFailed(PathBuf),
+    NoTrash(io::ErrorKind, String),
+}
+
+impl fmt::Display for FileOperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "Invalid file name: {name:?}"),
+            Self::AlreadyExists(name) => write!(f, "{name:?} already exists"),
+            Self::Refused(message) | Self::NoTrash(_, message) => f.write_str(message),
+            Self::ScanFailed(path) => write!(f, "Failed to scan {path:?}"),
+        }
+    }
+}
+
+impl Error for FileOperationError {}
+
+impl From<FileOperationError> for io::Error {
+    fn from(error: FileOperationError) -> Self {
+        let kind = match &error {
+            FileOperationError::InvalidName(_) | FileOperationError::Refused(_) => io::ErrorKind::InvalidInput,
+            FileOperationError::AlreadyExists(_) => io::ErrorKind::AlreadyExists,
+            FileOperationError::ScanFailed(_) => io::ErrorKind::Other,
+            FileOperationError::NoTrash(kind, _) => *kind,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// `error` from a file operation on `entry`, in the words of `labels`.
+pub(crate) fn describe_error(labels: &DirectoryComboBoxLabels, error: &io::Error, entry: &Path) -> String {
+    let entry_name = display_name(entry);
+    match error.get_ref().and_then(|e| e.downcast_ref::<FileOperationError>()) {
+        Some(FileOperationError::InvalidName(name)) => labels.invalid_name.replace("{name}", name),
+        Some(FileOperationError::AlreadyExists(name)) => labels.already_exists.replace("{name}", name),
+        Some(FileOperationError::Refused(_)) => labels.file_operation_refused.replace("{name}", &entry_name),
+        Some(FileOperationError::ScanFailed(_)) => labels.scan_failed.replace("{name}", &entry_name),
+        Some(FileOperationError::NoTrash(..)) => labels.no_trash.replace("{name}", &entry_name),
+        // Errors from the operating system
+        None => labels.file_operation_failed.replace("{name}", &entry_name).replace("{error}", &error.to_string()),
+    }
+}
+
+pub(crate) fn validate_name(name: &str) -> io::Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) => Ok(()),
+        _ => Err(FileOperationError::InvalidName(name.to_string()).into()),
+    }
+}
+
+/// The first of `base`, `base 2`, `base 3`... that doesn't exist in `dir`.
+pub(crate) fn unique_child_name(dir: &Path, base: &str) -> String {
+    let mut name = base.to_string();
+    let mut counter = 1;
+    while dir.join(&name).exists() {
+        counter += 1;
+        name = format!("{base} {counter}");
+    }
+    name
+}
+
+impl DirectoryComboBox {
+    /// Create a directory called `name` inside `parent`, and add it to the tree.
+    ///
+    /// `parent` must be a directory in the tree, scanned from disk.
+    pub fn create_directory<P: AsRef<Path>>(&mut self, parent: P, name: &str) -> io::Result<PathBuf> {
+        validate_name(name)?;
+        self.check_tree_directory(parent.as_ref())?;
+        let path = parent.as_ref().join(name);
+        fs::create_dir(&path)?;
+        self.insert_created(path)
+    }
+
+    /// Create an empty file called `name` inside `parent`, and add it to the tree.
+    ///
+    /// `parent` must be a directory in the tree, scanned from disk.
+    pub fn create_file<P: AsRef<Path>>(&mut self, parent: P, name: &str) -> io::Result<PathBuf> {
+        validate_name(name)?;
+        self.check_tree_directory(parent.as_ref())?;
+        let path = parent.as_ref().join(name);
+        fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+        self.insert_created(path)
+    }
+
+    /// Rename the file or directory at `path` to `new_name`, keeping it in the same directory.
+    ///
+    /// The tree, selection and favorites are updated to the new path. The roots, and nodes that
+    /// weren't scanned from disk, can't be renamed.
+    pub fn rename_path<P: AsRef<Path>>(&mut self, path: P, new_name: &str) -> io::Result<PathBuf> {
+        validate_name(new_name)?;
+        let path = path.as_ref();
+        self.check_scanned(path)?;
+        let parent = path.parent()
+            .filter(|p| self.is_tree_directory(p))
+            .ok_or_else(|| FileOperationError::Refused("Can't rename a root".to_string()))?;
+
+        let new_path = parent.join(new_name);
+        if new_path == path {
+            return Ok(new_path);
+        }
+        if new_path.exists() {
+            return Err(FileOperationError::AlreadyExists(new_name.to_string()).into());
+        }
+
+        fs::rename(path, &new_path)?;
+        if let Some(id) = self.find_node(path).map(|n| n.id()) {
+            self.tree_mut().rename(id, OsStr::new(new_name));
+        }
+        self.remap_paths(path, Some(&new_path));
+        Ok(new_path)
+    }
+
+    /// Move the file or directory at `path` to the freedesktop.org trash, and remove it from the tree.
+    ///
+    /// Entries on another volume than the home directory go to the trash at the top of their volume.
+    /// A selection inside `path` falls back to the directory `path` was in.
+    ///
+    /// `path` must be in the tree, scanned from disk. The roots can't be moved to the trash.
+    pub fn trash_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        self.check_scanned(path)?;
+        if !path.parent().is_some_and(|p| self.is_tree_directory(p)) {
+            return Err(FileOperationError::Refused("Can't move a root to the trash".to_string()).into());
+        }
+        trash::move_to_trash(path)?;
+        if let Some(id) = self.find_node(path).map(|n| n.id()) {
+            self.tree_mut().remove(id);
+        }
+        self.remap_paths(path, None);
+        Ok(())
+    }
+
+    /// The most recent error from a file operation started in the popups, if the last one failed,
+    /// in the words of the labels.
+    pub fn file_operation_error(&self) -> Option<&str> {
+        self.file_operation_error.as_deref()
+    }
+
+    /// Whether `dir` is a directory in the tree scanned from disk, rather than outside the roots.
+    pub(crate) fn is_tree_directory(&self, dir: &Path) -> bool {
+        self.find_node(dir).is_some_and(|n| n.is_dir() && n.metadata().is_some())
+    }
+
+    /// Fails unless `dir` is a directory in the tree, so nothing is written outside the roots.
+    fn check_tree_directory(&self, dir: &Path) -> io::Result<()> {
+        if self.is_tree_directory(dir) {
+            Ok(())
+        } else {
+            Err(FileOperationError::Refused(format!("{dir:?} isn't a directory scanned by the combo box")).into())
+        }
+    }
+
+    /// Fails unless `path` is in the tree and was scanned from disk. Nodes built from a list of
+    /// paths may not match the disk, so they aren't changed on it.
+    fn check_scanned(&self, path: &Path) -> io::Result<()> {
+        if self.find_node(path).is_some_and(|n| n.metadata().is_some()) {
+            Ok(())
+        } else {
+            Err(FileOperationError::Refused(format!("{path:?} isn't scanned by the combo box")).into())
+        }
+    }
+
+    /// Scan a newly created path and add it to the tree after its siblings.
+    ///
+    /// The directory containing `path` must be in the tree.
+    fn insert_created(&mut self, path: PathBuf) -> io::Result<PathBuf> {
+        let Some(parent) = path.parent().and_then(|p| self.find_node(p)).map(|n| n.id()) else {
+            return Err(FileOperationError::Refused(format!("{path:?} isn't in a directory of the combo box")).into());
+        };
+
+        self.tree_mut()
+            .insert_scanned(Some(parent), &path)
+            .ok_or_else(|| FileOperationError::ScanFailed(path.clone()))?;
+        Ok(path)
+    }
+
+    /// Point the selection and favorites inside `old` at `new`, or drop them if `new` is `None`.
+    fn remap_paths(&mut self, old: &Path, new: Option<&Path>) {
+        let remap = |p: &Path| -> Option<PathBuf> {
+            let rest = p.strip_prefix(old).ok()?;
+            Some(new.map_or_else(PathBuf::new, |n| n.join(rest)))
+        };
+
+        if let Some(selected_path) = &self.selected_path
+            && let Some(remapped) = remap(selected_path)
+        {
+            // Fall back to showing the directory the removed entry was in
+            self.selected_path = match new {
+                Some(_) => Some(remapped),
+                None => old.parent()
+                    .filter(|p| self.contains_path(p))
+                    .map(|p| p.to_path_buf()),
+            };
+        }
+
+        // In save mode, or once a directory the selectable predicate rejects was opened, the
+        // selected file isn't the selected path, so it is only changed if it was inside `old`
+        if let Some(selected_file) = &self.selected_file
+            && let Some(remapped) = remap(selected_file)
+        {
+            self.selected_file = match new {
+                Some(_) => Some(remapped),
+                None if !self.select_files_only && !self.save_mode => self.selected_path.clone()
+                    .filter(|p| self.unselectable_reason(p).is_none()),
+                None => None,
+            };
+        }
+
+        self.previews.retain(|p, _| !p.starts_with(old));
+
+        self.favorites = std::mem::take(&mut self.favorites)
+            .into_iter()
+            .filter_map(|f| match remap(&f) {
+                Some(remapped) => new.map(|_| remapped),
+                None => Some(f),
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,310 @@
+//! A combo box of nested popups over any hierarchy, such as asset categories or font families.
+
+use std::{hash::Hash, path::PathBuf, sync::Arc};
+
+use crate::{
+    DirectoryComboBoxLabels, DirectoryComboBoxStyle, DirectoryNode, PopupDirection, display_name,
+    popup::{self, PopupState, PopupTree},
+};
+
+/// A node that can be shown in a `HierarchyComboBox`.
+pub trait HierarchyNode: Sized {
+    /// Identifies the node among its siblings. It must stay the same from one frame to the next.
+    type Key: Clone + PartialEq + Hash;
+
+    fn key(&self) -> Self::Key;
+
+    /// The text of the node's row, and of the combo box when it is selected.
+    fn label(&self) -> String;
+
+    /// The children of a branch, or `None` for a leaf. A branch may have no children.
+    fn children(&self) -> Option<&[Self]>;
+}
+
+impl HierarchyNode for DirectoryNode {
+    type Key = PathBuf;
+
+    fn key(&self) -> PathBuf {
+        self.path().to_path_buf()
+    }
+
+    fn label(&self) -> String {
+        display_name(self.path()).into_owned()
+    }
+
+    fn children(&self) -> Option<&[DirectoryNode]> {
+        match self {
+            DirectoryNode::Directory(_, children, _) => Some(children),
+            DirectoryNode::File(..) => None,
+        }
+    }
+}
+
+/// A combo box showing `roots` in cascading popups, drawn like those of `DirectoryComboBox`, for
+/// hierarchies that aren't on the file system.
+///
+/// Entries are identified by the keys on the way down to them from a root. The file system
+/// features of `DirectoryComboBox`, like the context menu and save mode, aren't available.
+#[derive(Clone)]
+pub struct HierarchyComboBox<T: HierarchyNode> {
+    pub id: egui::Id,
+    /// The top-level entries, shared between clones.
+    pub roots: Arc<Vec<T>>,
+    /// The keys from a root to the entry clicked last. The popups of the branches along it are open.
+    open_path: Vec<T::Key>,
+    selected: Option<Vec<T::Key>>,
+    pub max_width: Option<f32>,
+    pub max_height: Option<f32>,
+    pub wrap_mode: Option<egui::TextWrapMode>,
+    pub select_leaves_only: bool,
+    pub back_button: bool,
+    pub popup_direction: PopupDirection,
+    pub labels: DirectoryComboBoxLabels,
+    /// The look of the rows and popups, with branches drawn as directories and leaves as files,
+    /// or one made from the current `Visuals` if `None`.
+    pub style: Option<DirectoryComboBoxStyle>,
+}
+
+impl<T: HierarchyNode> HierarchyComboBox<T> {
+    /// `roots` will be the top-level entries. Pass an `Arc` to share them with other combo boxes.
+    pub fn new(roots: impl Into<Arc<Vec<T>>>) -> Self {
+        Self {
+            id: egui::Id::new("hierarchy_combobox"),
+            roots: roots.into(),
+            open_path: Vec::new(),
+            selected: None,
+            max_width: None,
+            max_height: None,
+            wrap_mode: None,
+            select_leaves_only: false,
+            back_button: true,
+            popup_direction: PopupDirection::default(),
+            labels: DirectoryComboBoxLabels::default(),
+            style: None,
+        }
+    }
+
+    /// Change the id from the default: "hierarchy_combobox"
+    pub fn with_id(mut self, id: egui::Id) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Change the maximum height of each popup menu.
+    pub fn with_max_height(mut self, max_height: f32) -> Self {
+        self.max_height = Some(max_height);
+        self
+    }
+
+    /// Change the maximum width of each popup menu.
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Change the text wrap mode of the combo box.
+    pub fn with_wrap_mode(mut self, wrap_mode: egui::TextWrapMode) -> Self {
+        self.wrap_mode = Some(wrap_mode);
+        self
+    }
+
+    /// Only allow leaves to be selected. Clicking a branch still opens its popup. Default: false
+    pub fn select_leaves_only(mut self, select_leaves_only: bool) -> Self {
+        self.select_leaves_only = select_leaves_only;
+        self
+    }
+
+    /// Add a back button to the nested popups to close them, default: true
+    pub fn with_back_button(mut self, back_button: bool) -> Self {
+        self.back_button = back_button;
+        self
+    }
+
+    /// Set which side nested popups prefer to open on, default: `PopupDirection::Right`
+    pub fn with_popup_direction(mut self, popup_direction: PopupDirection) -> Self {
+        self.popup_direction = popup_direction;
+        self
+    }
+
+    /// Replace the text shown by the combo box, e.g. to translate it. Only `select`, `none`, `back` and `empty` are used.
+    pub fn with_labels(mut self, labels: DirectoryComboBoxLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Set the look of the rows and popups, instead of following the current `Visuals`.
+    pub fn with_style(mut self, style: DirectoryComboBoxStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// The keys from a root down to the selected entry.
+    pub fn selected(&self) -> Option<&[T::Key]> {
+        self.selected.as_deref()
+    }
+
+    pub fn selected_node(&self) -> Option<&T> {
+        self.node_at(self.selected.as_deref()?)
+    }
+
+    /// Select the entry reached by following `keys` down from the roots, and open the popups leading to it.
+    ///
+    /// Nothing changes if there is no such entry, or it is a branch and `select_leaves_only` is set.
+    /// Setting `keys` to `None` will clear the selection.
+    pub fn set_selection(&mut self, keys: Option<&[T::Key]>) {
+        let Some(keys) = keys else {
+            self.selected = None;
+            self.open_path.clear();
+            return;
+        };
+        if let Some(node) = self.node_at(keys)
+            && (node.children().is_none() || !self.select_leaves_only)
+        {
+            self.selected = Some(keys.to_vec());
+            self.open_path = keys.to_vec();
+        }
+    }
+
+    /// The entry reached by following `keys` down from the roots.
+    pub fn node_at(&self, keys: &[T::Key]) -> Option<&T> {
+        let (first, rest) = keys.split_first()?;
+        let root = self.roots.iter().find(|n| n.key() == *first)?;
+        rest.iter().try_fold(root, |node, key| node.children()?.iter().find(|n| n.key() == *key))
+    }
+
+    /// Whether the popup of the combo box is open.
+    pub fn is_open(&self, ctx: &egui::Context) -> bool {
+        egui::Popup::is_id_open(ctx, self.popup_id())
+    }
+
+    /// The id the root popup stores its open state under.
+    fn popup_id(&self) -> egui::Id {
+        self.id.with("popup")
+    }
+}
+
+/// Settings and state shared by every nested popup while the combo box is shown.
+struct HierarchyState<'a, T: HierarchyNode> {
+    roots: &'a [T],
+    open_path: &'a mut Vec<T::Key>,
+    selected: &'a mut Option<Vec<T::Key>>,
+    select_leaves_only: bool,
+    labels: &'a DirectoryComboBoxLabels,
+    back_button: bool,
+    popups: PopupState,
+}
+
+impl<'a, T: HierarchyNode> PopupTree for HierarchyState<'a, T> {
+    type Node = &'a T;
+
+    fn popup_state(&self) -> &PopupState {
+        &self.popups
+    }
+
+    fn popup_state_mut(&mut self) -> &mut PopupState {
+        &mut self.popups
+    }
+
+    fn rows(&mut self, branch: Option<&'a T>) -> Arc<[&'a T]> {
+        let nodes = match branch {
+            Some(branch) => branch.children().unwrap_or_default(),
+            None => self.roots,
+        };
+        nodes.iter().collect()
+    }
+
+    fn open_row(&self, rows: &[&'a T], _branch: Option<&'a T>, depth: usize) -> Option<usize> {
+        let key = self.open_path.get(depth)?;
+        rows.iter().position(|n| n.key() == *key)
+    }
+
+    fn is_branch(&self, node: &'a T) -> bool {
+        node.children().is_some()
+    }
+
+    fn label(&self, node: &'a T) -> String {
+        node.label()
+    }
+
+    fn is_current(&self, node: &'a T, depth: usize) -> bool {
+        self.open_path.len() == depth + 1 && self.open_path[depth] == node.key()
+    }
+
+    fn popup_id(&self, id: egui::Id, branch: &'a T) -> egui::Id {
+        id.with(branch.key())
+    }
+
+    fn header_ui(&mut self, ui: &mut egui::Ui, depth: usize) {
+        if depth == 0 {
+            if ui.selectable_label(self.selected.is_none(), &self.labels.none).clicked() {
+                self.popups.interacted = true;
+                self.open_path.clear();
+                *self.selected = None;
+            }
+        } else if self.back_button && ui.button(self.popups.style.back_text(&self.labels.back)).clicked() {
+            self.popups.interacted = true;
+            self.open_path.truncate(depth - 1);
+        }
+    }
+
+    fn empty_ui(&mut self, ui: &mut egui::Ui, _branch: Option<&'a T>) {
+        ui.label(&self.labels.empty);
+    }
+
+    /// Open the popups leading to `node`, and select it if it can be.
+    fn clicked(&mut self, ui: &mut egui::Ui, node: &'a T, depth: usize) {
+        let is_leaf = node.children().is_none();
+        self.open_path.truncate(depth);
+        self.open_path.push(node.key());
+        if is_leaf || !self.select_leaves_only {
+            *self.selected = Some(self.open_path.clone());
+        }
+        if is_leaf {
+            egui::Popup::close_all(ui.ctx());
+        }
+    }
+}
+
+impl<T: HierarchyNode> egui::Widget for &mut HierarchyComboBox<T> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let selected_text = match self.selected_node() {
+            Some(node) => node.label().into(),
+            None => self.labels.select.clone(),
+        };
+
+        let popup_id = self.popup_id();
+        let mut state = HierarchyState {
+            roots: &self.roots,
+            open_path: &mut self.open_path,
+            selected: &mut self.selected,
+            select_leaves_only: self.select_leaves_only,
+            labels: &self.labels,
+            back_button: self.back_button,
+            popups: PopupState {
+                style: self.style.clone().unwrap_or_else(|| DirectoryComboBoxStyle::from_visuals(ui.visuals())),
+                max_height: self.max_height,
+                max_width: self.max_width,
+                cascading: true,
+                popup_direction: self.popup_direction,
+                interacted: false,
+            },
+        };
+
+        let button = popup::ComboButton {
+            selected_text,
+            width: self.max_width,
+            wrap_mode: self.wrap_mode,
+        };
+        let style = state.popups.style.clone();
+        let cb_response = popup::combo_box(ui, popup_id, button, &style, |ui| {
+            popup::level_ui(ui, &mut state, None, 0, self.id.with("child"));
+        }).response;
+
+        // There was a click and no popups were clicked -> close all popups
+        if ui.ctx().input(|i| i.pointer.any_click()) && !cb_response.clicked() && !state.popups.interacted {
+            egui::Popup::close_id(ui.ctx(), popup_id);
+        }
+
+        cb_response
+    }
+}
@@ -0,0 +1,63 @@
+//! A lookup table from paths to the nodes of the tree, so they can be found without walking it.
+
+use std::{cell::RefCell, collections::HashMap, hash::{BuildHasher, RandomState}, path::{Path, PathBuf}};
+
+use crate::tree::{DirectoryTree, NodeId};
+
+/// Maps the hash of each path in the tree to its node, so the paths themselves aren't stored.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PathIndex {
+    hasher: RandomState,
+    /// Rebuilt in place if it turns out not to match the tree.
+    ids: RefCell<HashMap<u64, NodeId>>,
+}
+
+impl PathIndex {
+    pub(crate) fn new(tree: &DirectoryTree) -> Self {
+        let index = Self::default();
+        index.rebuild(tree);
+        index
+    }
+
+    fn rebuild(&self, tree: &DirectoryTree) {
+        fn add(ids: &mut HashMap<u64, NodeId>, hasher: &RandomState, tree: &DirectoryTree, id: NodeId, path: &mut PathBuf) {
+            // A path under several roots resolves to the first, like a search through the roots would
+            ids.entry(hasher.hash_one(&*path)).or_insert(id);
+            for &child in tree.children(id) {
+                path.push(tree.name(child));
+                add(ids, hasher, tree, child, path);
+                path.pop();
+            }
+        }
+
+        let mut ids = HashMap::with_capacity(tree.len());
+        for &root in tree.roots() {
+            add(&mut ids, &self.hasher, tree, root, &mut PathBuf::from(tree.name(root)));
+        }
+        *self.ids.borrow_mut() = ids;
+    }
+
+    fn lookup(&self, tree: &DirectoryTree, path: &Path) -> Option<Result<NodeId, NodeId>> {
+        let id = *self.ids.borrow().get(&self.hasher.hash_one(path))?;
+        Some(if tree.has_path(id, path) { Ok(id) } else { Err(id) })
+    }
+
+    /// The node at `path` in `tree`, which should be the tree this index was built from.
+    ///
+    /// If the hash of `path` leads to another node, the tree has changed since, so the index is rebuilt from it.
+    pub(crate) fn get(&self, tree: &DirectoryTree, path: &Path) -> Option<NodeId> {
+        match self.lookup(tree, path)? {
+            Ok(id) => return Some(id),
+            Err(_) => self.rebuild(tree),
+        }
+        match self.lookup(tree, path)? {
+            Ok(id) => Some(id),
+            // Another path with the same hash
+            Err(_) => tree.find(path),
+        }
+    }
+
+    pub(crate) fn contains(&self, tree: &DirectoryTree, path: &Path) -> bool {
+        self.get(tree, path).is_some()
+    }
+}
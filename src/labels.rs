@@ -0,0 +1,95 @@
+/// Every piece of text the combo box shows, so it can be translated or reworded.
+///
+/// Labels containing `{name}` have it replaced with the name of the entry they refer to. A `HierarchyComboBox`
+/// only shows `select`, `none`, `back` and `empty`.
+#[derive(Clone)]
+pub struct DirectoryComboBoxLabels {
+    /// Shown on the button when nothing is selected.
+    pub select: egui::WidgetText,
+    /// The entry clearing the selection.
+    pub none: String,
+    pub back: String,
+    /// Shown in the popup of a directory with no visible entries.
+    pub empty: String,
+
+    pub copy_path: String,
+    pub copy_relative_path: String,
+    pub pin: String,
+    pub unpin: String,
+    pub refresh_folder: String,
+    pub new_folder: String,
+    pub new_file: String,
+    pub rename: String,
+    pub delete: String,
+    /// The question asked before moving `{name}` to the trash.
+    pub trash_confirmation: String,
+    pub move_to_trash: String,
+    pub cancel: String,
+    /// The name given to a newly created folder, before the user renames it.
+    pub new_folder_name: String,
+    /// The name given to a newly created file, before the user renames it.
+    pub new_file_name: String,
+    /// Shown when a new name isn't a single file name.
+    pub invalid_name: String,
+    pub already_exists: String,
+    /// Shown when asked to change a root, or an entry the combo box didn't scan from disk.
+    pub file_operation_refused: String,
+    /// Shown when a new entry was created but couldn't be read back.
+    pub scan_failed: String,
+    /// Shown when there is no trash to move `{name}` to, or it couldn't be created.
+    pub no_trash: String,
+    /// Shown when the system refused a file operation, with `{error}` replaced by its description.
+    pub file_operation_failed: String,
+
+    /// The hint in the file name field of save mode.
+    pub file_name_hint: String,
+    pub save: String,
+    /// The warning shown in save mode when `{name}` already exists.
+    pub overwrite_warning: String,
+    pub replace: String,
+
+    /// The tooltip shown when a dropped file can't be selected.
+    pub drop_rejected: String,
+    /// The modification time in hover previews, with `{time}` replaced by the time in UTC.
+    pub modified: String,
+}
+
+impl Default for DirectoryComboBoxLabels {
+    fn default() -> Self {
+        Self {
+            select: "Select".into(),
+            none: "None".to_string(),
+            back: "Back".to_string(),
+            empty: "Empty".to_string(),
+
+            copy_path: "Copy path".to_string(),
+            copy_relative_path: "Copy relative path".to_string(),
+            pin: "Pin".to_string(),
+            unpin: "Unpin".to_string(),
+            refresh_folder: "Refresh this folder".to_string(),
+            new_folder: "New folder".to_string(),
+            new_file: "New file".to_string(),
+            rename: "Rename".to_string(),
+            delete: "Delete".to_string(),
+            trash_confirmation: "Move \"{name}\" to the trash?".to_string(),
+            move_to_trash: "Move to trash".to_string(),
+            cancel: "Cancel".to_string(),
+            new_folder_name: "New folder".to_string(),
+            new_file_name: "New file".to_string(),
+            invalid_name: "\"{name}\" isn't a valid name".to_string(),
+            already_exists: "\"{name}\" already exists".to_string(),
+            file_operation_refused: "\"{name}\" can't be changed from here".to_string(),
+            scan_failed: "\"{name}\" was created but couldn't be read".to_string(),
+            no_trash: "There is no trash to move \"{name}\" to".to_string(),
+            file_operation_failed: "Couldn't change \"{name}\": {error}".to_string(),
+
+            file_name_hint: "File name".to_string(),
+            save: "Save".to_string(),
+            overwrite_warning: "\"{name}\" already exists. Replace it?".to_string(),
+            replace: "Replace".to_string(),
+
+            drop_rejected: "Only files inside these folders can be selected".to_string(),
+            modified: "Modified {time} UTC".to_string(),
+        }
+    }
+}
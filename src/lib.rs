@@ -1,41 +1,194 @@
-use std::{path::{Path, PathBuf}, sync::Arc};
+use std::{borrow::Cow, cell::OnceCell, collections::HashMap, path::{Path, PathBuf}, sync::Arc, time::SystemTime};
 
 use egui::RichText;
 use dunce::canonicalize;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum DirectoryNode {
-    File(PathBuf),
-    Directory(PathBuf, Vec<DirectoryNode>),
+mod file_ops;
+mod hierarchy;
+mod index;
+mod labels;
+mod path_list;
+mod popup;
+mod preview;
+mod style;
+mod time;
+mod trash;
+mod traverse;
+mod tree;
+
+pub use hierarchy::{HierarchyComboBox, HierarchyNode};
+use index::PathIndex;
+use popup::{PopupState, PopupTree};
+pub use labels::DirectoryComboBoxLabels;
+pub use style::DirectoryComboBoxStyle;
+pub use traverse::{Nodes, Visit, VisitControl};
+pub use tree::{DirectoryTree, NodeId, TreeNode};
+use preview::FilePreview;
+
+/// The width of each column in the columns layout, unless `max_width` is set.
+const COLUMN_WIDTH: f32 = 180.0;
+
+/// A function deciding whether a path is shown in the combo box.
+pub type PathFilter = Arc<dyn Fn(&Path) -> bool>;
+
+/// A function returning why a node can't be selected, or `None` if it can.
+pub type SelectablePredicate = Arc<dyn Fn(TreeNode<'_>) -> Option<String>>;
+
+/// A path relative to one of the roots, which stays valid when the roots move between machines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RootRelativePath {
+    /// The index of the root in the `DirectoryTree::roots` of the combo box's tree.
+    ///
+    /// `new_from_paths` keeps the roots in the order given, and `new_from_path` sorts them by name,
+    /// so the index is the same on every machine with the same entries.
+    pub root: usize,
+    /// The path from the root, with components separated by forward slashes. Empty for the root itself.
+    pub path: String,
 }
 
-impl DirectoryNode {
-    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+/// How `select_next_file` and `select_previous_file` move through the tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NavigationMode {
+    /// Cycle through the files in the directory of the selected file.
+    #[default]
+    Folder,
+    /// Step through every file under the roots in display order, moving into the next directory
+    /// when one runs out.
+    DepthFirst(NavigationWrap),
+}
+
+/// What depth-first navigation does after the last file, or before the first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NavigationWrap {
+    /// Keep the selection where it is.
+    #[default]
+    Stop,
+    /// Go back to the first file of the same root, without leaving it.
+    WithinRoot,
+    /// Go back to the first file of the first root.
+    AcrossRoots,
+}
+
+/// How the contents of directories are presented in the popups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PopupLayout {
+    /// Each opened directory gets its own popup, to the right of the one containing it.
+    #[default]
+    Cascading,
+    /// A single popup replaces its contents when a directory is entered, with the current path
+    /// and a Back row at the top. Suited to narrow panels and deep trees.
+    DrillDown,
+    /// A single popup with a column per opened directory, side by side and scrolling horizontally,
+    /// like the column view of macOS Finder.
+    Columns,
+}
+
+/// Which side of their parent row nested popups open on in the cascading layout.
+///
+/// A popup that would overflow the screen on this side flips to the other one, and the
+/// popups nested in it keep going in the new direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PopupDirection {
+    #[default]
+    Right,
+    Left,
+}
+
+impl PopupDirection {
+    fn aligns(self) -> &'static [egui::RectAlign] {
+        match self {
+            PopupDirection::Right => &[egui::RectAlign::RIGHT_START, egui::RectAlign::LEFT_START],
+            PopupDirection::Left => &[egui::RectAlign::LEFT_START, egui::RectAlign::RIGHT_START],
+        }
+    }
+}
+
+/// A custom entry added to the right-click menu of every entry in the popups.
+#[derive(Clone)]
+pub struct ContextMenuAction {
+    pub label: String,
+    /// Called with the node that was right-clicked when the entry is clicked.
+    pub action: Arc<dyn Fn(&DirectoryNode)>,
+}
+
+impl ContextMenuAction {
+    pub fn new(label: impl Into<String>, action: impl Fn(&DirectoryNode) + 'static) -> Self {
+        Self { label: label.into(), action: Arc::new(action) }
+    }
+}
+
+/// File system metadata captured when a node is scanned, so it doesn't need to be read every frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeMetadata {
+    /// The length of a file in bytes. `None` for directories.
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub readonly: bool,
+    /// Whether the path the node was scanned from is a symbolic link.
+    pub symlink: bool,
+    /// Whether the metadata, or the entries of a directory, couldn't be read due to permissions.
+    pub permission_denied: bool,
+}
+
+impl NodeMetadata {
+    /// Read the metadata of `path`, following symbolic links.
+    pub fn read<P: AsRef<Path>>(path: P) -> Self {
         let path = path.as_ref();
-        
-        if !path.exists() {
-            std::fs::create_dir_all(&path).ok()?;
+        let symlink = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+
+        match std::fs::metadata(path) {
+            Ok(m) => Self {
+                size: m.is_file().then_some(m.len()),
+                modified: m.modified().ok(),
+                created: m.created().ok(),
+                readonly: m.permissions().readonly(),
+                symlink,
+                permission_denied: false,
+            },
+            Err(e) => Self {
+                symlink,
+                permission_denied: e.kind() == std::io::ErrorKind::PermissionDenied,
+                ..Default::default()
+            },
         }
+    }
+}
 
-        let path = canonicalize(path).ok()?;
+/// A file or directory, with its metadata if it was scanned from disk.
+///
+/// Breaking change: the metadata was added as the last field of both variants, so patterns
+/// written as `File(path)` or `Directory(path, children)` need a trailing `_` or `..`.
+#[derive(Debug, Clone)]
+pub enum DirectoryNode {
+    File(PathBuf, Option<NodeMetadata>),
+    Directory(PathBuf, Vec<DirectoryNode>, Option<NodeMetadata>),
+}
 
-        if path.is_dir() {
-            let mut children = Vec::new();
-            if let Ok(entries) = std::fs::read_dir(&path) {
-                for entry in entries.flatten() {
-                    // entry should start with path, else it is probably a symlink which we ignore
-                    if entry.path().starts_with(&path) {
-                        children.push(DirectoryNode::try_from_path(entry.path())?);
-                    }
-                }
+/// Nodes are equal if their paths and children are. The metadata is ignored, so two scans of
+/// the same tree are equal even if timestamps changed in between.
+impl PartialEq for DirectoryNode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DirectoryNode::File(a, _), DirectoryNode::File(b, _)) => a == b,
+            (DirectoryNode::Directory(a, a_children, _), DirectoryNode::Directory(b, b_children, _)) => {
+                a == b && a_children == b_children
             }
-            Some(DirectoryNode::Directory(path, children))
-        } else if path.is_file() {
-            Some(DirectoryNode::File(path))
-        } else {
-            None
+            _ => false,
         }
     }
+}
+
+impl Eq for DirectoryNode {}
+
+impl DirectoryNode {
+    /// Scan `path` from disk, creating it as a directory if it doesn't exist.
+    ///
+    /// Symbolic links are only followed if they point below the directory containing them.
+    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let tree = DirectoryTree::from_path(path)?;
+        Some(tree.to_node(tree.roots()[0]))
+    }
 
     pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
        Self::try_from_path(&path).unwrap_or_else(|| {
@@ -44,18 +197,53 @@ impl DirectoryNode {
                path.as_ref()
            )
        })
-}   
+}
     pub fn path(&self) -> &Path {
         match self {
-            DirectoryNode::File(p) => p,
-            DirectoryNode::Directory(p, _) => p,
+            DirectoryNode::File(p, _) => p,
+            DirectoryNode::Directory(p, _, _) => p,
+        }
+    }
+
+    /// The metadata captured when this node was scanned, if it was scanned from disk.
+    pub fn metadata(&self) -> Option<&NodeMetadata> {
+        match self {
+            DirectoryNode::File(_, metadata) => metadata.as_ref(),
+            DirectoryNode::Directory(_, _, metadata) => metadata.as_ref(),
         }
     }
 
+    /// The cached size of a file in bytes.
+    pub fn size(&self) -> Option<u64> {
+        self.metadata().and_then(|m| m.size)
+    }
+
+    /// The cached modification time.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.metadata().and_then(|m| m.modified)
+    }
+
+    /// The cached creation time, on platforms that record it.
+    pub fn created(&self) -> Option<SystemTime> {
+        self.metadata().and_then(|m| m.created)
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.metadata().is_some_and(|m| m.readonly)
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.metadata().is_some_and(|m| m.symlink)
+    }
+
+    pub fn is_permission_denied(&self) -> bool {
+        self.metadata().is_some_and(|m| m.permission_denied)
+    }
+
     pub fn find_parent_directory(&self, path: &Path) -> Option<&DirectoryNode> {
         match self {
-            DirectoryNode::File(_) => None,
-            DirectoryNode::Directory(dir_path, children) => {
+            DirectoryNode::File(_, _) => None,
+            DirectoryNode::Directory(dir_path, children, _) => {
                 if path.starts_with(dir_path) {
                     for child in children {
                         if let Some(found) = child.find_parent_directory(path) {
@@ -69,16 +257,34 @@ impl DirectoryNode {
         }
     }
 
+    /// Rescan this node, and its metadata, from disk.
+    ///
+    /// Returns false, leaving the node unchanged, if it wasn't scanned from disk, like the nodes of
+    /// `from_path_list`, or its path no longer exists.
+    pub fn refresh(&mut self) -> bool {
+        if self.metadata().is_none() || !self.path().exists() {
+            return false;
+        }
+
+        match Self::try_from_path(self.path()) {
+            Some(node) => {
+                *self = node;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn find_node_of_path(&self, path: &Path) -> Option<&DirectoryNode> {
         match self {
-            DirectoryNode::File(p) => {
+            DirectoryNode::File(p, _) => {
                 if p == path {
                     Some(self)
                 } else {
                     None
                 }
             }
-            DirectoryNode::Directory(dir_path, children) => {
+            DirectoryNode::Directory(dir_path, children, _) => {
                 if dir_path == path {
                     return Some(self);
                 }
@@ -98,14 +304,42 @@ pub struct DirectoryComboBox {
     pub id: egui::Id,
     selected_path: Option<PathBuf>,
     selected_file: Option<PathBuf>,
-    pub roots: Vec<DirectoryNode>,
+    favorites: Vec<PathBuf>,
+    /// The entries, shared between clones until one of them changes the tree.
+    tree: Arc<DirectoryTree>,
+    /// Built from `tree` the first time a path is looked up, and dropped whenever it changes.
+    index: OnceCell<PathIndex>,
+    /// The entries of each directory the filter lets through, dropped along with the index.
+    visible_rows: VisibleRows,
     pub max_width: Option<f32>,
     pub max_height: Option<f32>,
     pub wrap_mode: Option<egui::TextWrapMode>,
     pub show_extensions: bool,
-    pub filter: Option<Arc<dyn Fn(&Path) -> bool>>,
+    /// The popups remember which entries it let through, see `refilter`.
+    pub filter: Option<PathFilter>,
+    pub selectable: Option<SelectablePredicate>,
     pub select_files_only: bool,
-    pub back_button: bool
+    pub back_button: bool,
+    pub layout: PopupLayout,
+    pub popup_direction: PopupDirection,
+    pub navigation: NavigationMode,
+    pub context_menu_actions: Vec<ContextMenuAction>,
+    pub file_operations: bool,
+    file_operation_error: Option<String>,
+    pub save_mode: bool,
+    pub save_extension: Option<String>,
+    save_name: String,
+    save_overwrite: Option<PathBuf>,
+    pub drag_and_drop: bool,
+    pub hover_previews: bool,
+    previews: HashMap<PathBuf, FilePreview>,
+    pub required: bool,
+    pub default_selection: Option<PathBuf>,
+    pub labels: DirectoryComboBoxLabels,
+    /// The look of the rows and popups, or one made from the current `Visuals` if `None`.
+    pub style: Option<DirectoryComboBoxStyle>,
+    /// Whether to open (true) or close (false) the popups the next time the combo box is shown.
+    popup_request: Option<bool>,
 }
 
 impl Default for DirectoryComboBox {
@@ -113,46 +347,70 @@ impl Default for DirectoryComboBox {
         Self {
             selected_path: None,
             selected_file: None,
-            roots: Vec::new(),
+            favorites: Vec::new(),
+            tree: Arc::default(),
+            index: OnceCell::new(),
+            visible_rows: VisibleRows::default(),
             id: egui::Id::new("directory_combobox"),
             max_height: None,
             max_width: None,
             wrap_mode: None,
             show_extensions: true,
             filter: None,
+            selectable: None,
             select_files_only: false,
-            back_button: true
+            back_button: true,
+            layout: PopupLayout::default(),
+            popup_direction: PopupDirection::default(),
+            navigation: NavigationMode::default(),
+            context_menu_actions: Vec::new(),
+            file_operations: false,
+            file_operation_error: None,
+            save_mode: false,
+            save_extension: None,
+            save_name: String::new(),
+            save_overwrite: None,
+            drag_and_drop: false,
+            hover_previews: false,
+            previews: HashMap::new(),
+            required: false,
+            default_selection: None,
+            labels: DirectoryComboBoxLabels::default(),
+            style: None,
+            popup_request: None,
         }
     }
 }
 
 impl DirectoryComboBox {
-    /// If `path` is a directory, its children will be the selectable values.
-    /// 
+    /// If `path` is a directory, its children will be the selectable values, sorted by name.
+    ///
     /// If `path` is a file, it will be the only selectable value.
     pub fn new_from_path<P: AsRef<Path>>(path: P) -> Self {
-        let root_node = DirectoryNode::from_path(path);
-
-        let roots = match root_node {
-            DirectoryNode::Directory(_, children) => children,
-            DirectoryNode::File(_) => vec![root_node],
-        };
-
-        Self { roots, ..Default::default() }
+        let tree = DirectoryTree::from_directory_entries(path.as_ref()).unwrap_or_else(|| {
+            panic!("Failed to make DirectoryTree from path: {:?}", path.as_ref())
+        });
+        Self::new_from_tree(tree)
     }
 
     /// `paths` will each be a root node in the combo box.
     pub fn new_from_paths<P: AsRef<Path>>(paths: &[P]) -> Self {
-        let mut roots = Vec::new();
-        for path in paths {
-            let root_node = DirectoryNode::from_path(path);
-            roots.push(root_node);
-        }
-        Self { roots, ..Default::default() }
+        let tree = DirectoryTree::from_paths(paths).unwrap_or_else(|| {
+            let paths: Vec<&Path> = paths.iter().map(AsRef::as_ref).collect();
+            panic!("Failed to make DirectoryTree from paths: {paths:?}")
+        });
+        Self::new_from_tree(tree)
     }
 
+    /// `roots` will be the top-level entries.
     pub fn new_from_nodes(roots: Vec<DirectoryNode>) -> Self {
-        Self { roots, ..Default::default() }
+        Self::new_from_tree(DirectoryTree::from_nodes(&roots))
+    }
+
+    /// The roots of `tree` will be the top-level entries. Pass an `Arc` to share one scan between
+    /// several combo boxes.
+    pub fn new_from_tree(tree: impl Into<Arc<DirectoryTree>>) -> Self {
+        Self { tree: tree.into(), ..Default::default() }
     }
 
     /// Change the id from the default: "directory_combobox"
@@ -180,11 +438,22 @@ impl DirectoryComboBox {
     }
 
     /// Set a filter function to determine which files are shown.
-    pub fn with_filter(mut self, filter: Arc<dyn Fn(&Path) -> bool>) -> Self {
+    ///
+    /// The popups only check each entry against it once. If it depends on state that changes, like
+    /// a search query, call `refilter` after each change.
+    pub fn with_filter(mut self, filter: PathFilter) -> Self {
         self.filter = Some(filter);
         self
     }
 
+    /// Set a function returning why an entry can't be selected, or `None` if it can.
+    ///
+    /// Unlike the filter, rejected entries are still shown, greyed out with the reason as a tooltip.
+    pub fn with_selectable(mut self, selectable: SelectablePredicate) -> Self {
+        self.selectable = Some(selectable);
+        self
+    }
+
     /// If true, only files can be selected. If false, directories can also be selected, default: false
     pub fn select_files_only(mut self, select_files_only: bool) -> Self {
         self.select_files_only = select_files_only;
@@ -197,16 +466,193 @@ impl DirectoryComboBox {
         self
     }
 
+    /// If true, the selection can't be cleared and the "None" entry is hidden, default: false
+    ///
+    /// When nothing is selected, the default selection or else the first file is selected automatically.
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// The path selected automatically in required mode when nothing is selected.
+    pub fn with_default_selection<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.default_selection = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Replace the text shown by the combo box, e.g. to translate it.
+    pub fn with_labels(mut self, labels: DirectoryComboBoxLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Set the look of the rows and popups, instead of following the current `Visuals`.
+    pub fn with_style(mut self, style: DirectoryComboBoxStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// Set the pinned favorites, shown as shortcuts at the top of the combo box.
+    pub fn with_favorites<P: AsRef<Path>>(mut self, favorites: &[P]) -> Self {
+        self.set_favorites(favorites);
+        self
+    }
+
+    /// Set how directories are presented in the popups, default: `PopupLayout::Cascading`
+    pub fn with_layout(mut self, layout: PopupLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set which side nested popups prefer to open on, default: `PopupDirection::Right`
+    pub fn with_popup_direction(mut self, direction: PopupDirection) -> Self {
+        self.popup_direction = direction;
+        self
+    }
+
+    /// Set how `select_next_file` and `select_previous_file` move through the tree, default: `NavigationMode::Folder`
+    pub fn with_navigation(mut self, navigation: NavigationMode) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
+    /// Add a custom entry to the right-click menu of the popup entries.
+    pub fn with_context_menu_action(mut self, action: ContextMenuAction) -> Self {
+        self.context_menu_actions.push(action);
+        self
+    }
+
+    /// Allow creating, renaming and trashing entries from their right-click menu, default: false
+    pub fn with_file_operations(mut self, file_operations: bool) -> Self {
+        self.file_operations = file_operations;
+        self
+    }
+
+    /// If true, the popups pick a path to save to, default: false
+    ///
+    /// The user opens the directory to save in and types a file name, which may not exist yet.
+    /// `selected` returns the chosen path.
+    pub fn save_mode(mut self, save_mode: bool) -> Self {
+        self.save_mode = save_mode;
+        self
+    }
+
+    /// In save mode, append `extension` to the file name if it doesn't already end with it.
+    pub fn with_save_extension(mut self, extension: impl Into<String>) -> Self {
+        self.save_extension = Some(extension.into().trim_start_matches('.').to_string());
+        self
+    }
+
+    /// Allow dragging files out of the popups, and dropping files onto the combo box, default: false
+    ///
+    /// A dragged file's payload is its `PathBuf`, see `egui::Response::dnd_release_payload`.
+    /// Files dropped from outside the app are selected if they are inside the roots.
+    pub fn with_drag_and_drop(mut self, drag_and_drop: bool) -> Self {
+        self.drag_and_drop = drag_and_drop;
+        self
+    }
+
+    /// Show a tooltip with the path, size and modification time of hovered files, default: false
+    ///
+    /// Images get a thumbnail, loaded through egui's image loaders, and text files show their first lines.
+    pub fn with_hover_previews(mut self, hover_previews: bool) -> Self {
+        self.hover_previews = hover_previews;
+        self
+    }
+
     /// If `select_files_only` is true, this will return the last selected file, if any.
-    /// 
+    ///
     /// If `select_files_only` is false, this will return the selected path (file or dir), if any.
+    ///
+    /// In save mode, this will return the path to save to, if one was chosen.
     pub fn selected(&self) -> Option<&Path> {
-        self.selected_file.as_ref().map(|p| p.as_path())
+        self.selected_file.as_deref()
+    }
+
+    /// The selection relative to the root it is in, see `selected`.
+    pub fn selected_relative(&self) -> Option<RootRelativePath> {
+        self.relative_path(self.selected()?)
+    }
+
+    /// `path` relative to the first root containing it.
+    pub fn relative_path<P: AsRef<Path>>(&self, path: P) -> Option<RootRelativePath> {
+        let path = path.as_ref();
+        self.tree.roots().iter().enumerate().find_map(|(i, &root)| {
+            let relative = path.strip_prefix(self.tree.name(root)).ok()?;
+            let components = relative.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>();
+            Some(RootRelativePath { root: i, path: components.join("/") })
+        })
+    }
+
+    /// The absolute path of `relative` on this machine, if it is a valid path inside its root.
+    ///
+    /// The path doesn't need to exist.
+    pub fn absolute_path(&self, relative: &RootRelativePath) -> Option<PathBuf> {
+        let mut path = self.tree.path(*self.tree.roots().get(relative.root)?);
+        for component in relative.path.split('/').filter(|c| !c.is_empty()) {
+            // Don't allow escaping the root, or components that are more than one name on this platform
+            if file_ops::validate_name(component).is_err() {
+                return None;
+            }
+            path.push(component);
+        }
+        Some(path)
+    }
+
+    /// Set the selection to `path` inside the root at `root`, with components separated by forward slashes.
+    ///
+    /// This behaves like `set_selection`, and does nothing if the path isn't inside the root.
+    pub fn set_selection_relative(&mut self, root: usize, path: &str) {
+        let relative = RootRelativePath { root, path: path.to_string() };
+        if let Some(path) = self.absolute_path(&relative) {
+            self.set_selection(Some(path));
+        }
     }
 
     /// This will always return the selected path, used to display the open popups.
     pub fn selected_path(&self) -> Option<&Path> {
-        self.selected_path.as_ref().map(|p| p.as_path())
+        self.selected_path.as_deref()
+    }
+
+    /// The pinned favorites, in the order they are shown.
+    pub fn favorites(&self) -> &[PathBuf] {
+        &self.favorites
+    }
+
+    /// Replace the pinned favorites.
+    ///
+    /// Paths that aren't in the tree and can't be canonicalized are ignored.
+    pub fn set_favorites<P: AsRef<Path>>(&mut self, favorites: &[P]) {
+        self.favorites.clear();
+        for favorite in favorites {
+            self.add_favorite(favorite);
+        }
+    }
+
+    /// Pin `path` to the favorites. Returns false if it was already pinned, or isn't in the tree and can't be canonicalized.
+    pub fn add_favorite<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        match self.resolve_path(path.as_ref()) {
+            Some(p) if !self.favorites.contains(&p) => {
+                self.favorites.push(p);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Unpin `path` from the favorites. Returns false if it wasn't pinned.
+    pub fn remove_favorite<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let path = self.resolve_path(path.as_ref()).unwrap_or_else(|| path.as_ref().to_path_buf());
+        let len = self.favorites.len();
+        self.favorites.retain(|f| f != &path);
+        self.favorites.len() != len
+    }
+
+    /// Whether `path` is pinned to the favorites.
+    pub fn is_favorite<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.resolve_path(path.as_ref()).is_some_and(|p| self.favorites.contains(&p))
     }
 
     /// Add a bacl button to the popup menus to go to the previous directory, default: true
@@ -215,126 +661,263 @@ impl DirectoryComboBox {
         self
     }
 
+    /// Whether `node` is shown in the popups and can be clicked.
+    ///
+    /// Directories the selectable predicate rejects can still be opened, to reach the entries inside.
+    fn can_enter(&self, node: TreeNode) -> bool {
+        node.is_dir() || (self.filter.as_ref().is_none_or(|f| f(&node.path())) && self.is_unlocked(node))
+    }
+
+    /// Whether file navigation can land on `node`: it must be selectable, and not a directory in files-only mode.
+    fn can_navigate_to(&self, node: TreeNode) -> bool {
+        self.can_enter(node)
+            && self.is_unlocked(node)
+            && !(self.select_files_only && node.is_dir())
+    }
+
+    /// Whether the selectable predicate accepts `node`.
+    fn is_unlocked(&self, node: TreeNode) -> bool {
+        self.selectable.as_ref().is_none_or(|s| s(node).is_none())
+    }
+
+    /// The first node in `nodes` accepted by `accept`, starting after `current` and going forward
+    /// or backward. `nodes` holds each node with the index of the root it is under.
     fn navigate_nodes(
-        nodes: &[DirectoryNode],
+        nodes: &[(usize, TreeNode)],
+        current: Option<usize>,
         forward: bool,
-        filter: Option<&Arc<dyn Fn(&Path) -> bool>>,
-        selected_path: &mut Option<PathBuf>,
-        selected_file: &mut Option<PathBuf>,
-    ) {
-        if let Some(selected_file_unwrap) = &selected_file {
-            let children_iter = if forward {
-                Box::new(nodes.iter()) as Box<dyn Iterator<Item = &DirectoryNode>>
-            } else {
-                Box::new(nodes.iter().rev()) as Box<dyn Iterator<Item = &DirectoryNode>>
-            };
-
-            let mut found_selected = false;
-            for child in children_iter {
-                if let DirectoryNode::File(file_path) = child {
-                    if file_path == selected_file_unwrap {
-                        found_selected = true;
-                    } else if found_selected && filter.as_ref().map_or(true, |f| f(file_path)) {
-                        *selected_path = Some(file_path.clone());
-                        *selected_file = Some(file_path.clone());
-                        return;
-                    }
-                }
-            }
-            if found_selected {
-                // Wrap around to the start/end of the list
-                if forward {
-                    for child in nodes {
-                        if let DirectoryNode::File(file_path) = child {
-                            if filter.as_ref().map_or(true, |f| f(file_path)) {
-                                *selected_path = Some(file_path.clone());
-                                *selected_file = Some(file_path.clone());
-                            }
-                            return;
-                        }
-                    }
+        wrap: NavigationWrap,
+        accept: impl Fn(TreeNode) -> bool,
+    ) -> Option<PathBuf> {
+        let order: Vec<usize> = match current {
+            Some(i) => {
+                let (after, wrapped): (Vec<usize>, Vec<usize>) = if forward {
+                    ((i + 1..nodes.len()).collect(), (0..i).collect())
                 } else {
-                    for child in nodes.iter().rev() {
-                        if let DirectoryNode::File(file_path) = child {
-                            if filter.as_ref().map_or(true, |f| f(file_path)) {
-                                *selected_path = Some(file_path.clone());
-                                *selected_file = Some(file_path.clone());
-                            }
-                            return;
-                        }
-                    }
-                }
+                    ((0..i).rev().collect(), (i + 1..nodes.len()).rev().collect())
+                };
+                let wrapped = match wrap {
+                    NavigationWrap::Stop => Vec::new(),
+                    _ => wrapped,
+                };
+                let root = nodes[i].0;
+                after.into_iter()
+                    .chain(wrapped)
+                    .filter(|j| wrap != NavigationWrap::WithinRoot || nodes[*j].0 == root)
+                    .collect()
             }
+            None if forward => (0..nodes.len()).collect(),
+            None => (0..nodes.len()).rev().collect(),
+        };
+
+        order.into_iter()
+            .find(|j| accept(nodes[*j].1))
+            .map(|j| nodes[j].1.path())
+    }
+
+    /// The nodes in the same directory as `id`, including `id` itself.
+    fn siblings(&self, id: NodeId) -> &[NodeId] {
+        match self.tree.parent(id) {
+            Some(parent) => self.tree.children(parent),
+            None => self.tree.roots(),
         }
     }
 
-    fn navigate_folder(&mut self, forward: bool) {
-        if let Some(selected_file) = &self.selected_file {
-            for root in &self.roots {
-                if root.path() == selected_file {
-                    // Selected file is a root
-                    Self::navigate_nodes(
-                        &self.roots,
-                        forward,
-                        self.filter.as_ref(),
-                        &mut self.selected_path,
-                        &mut self.selected_file
-                    );
-                    return;
-                }
-            }
-            
-            for root in &self.roots {
-                if let Some(parent) = root.find_parent_directory(&selected_file) {
-                    if let DirectoryNode::Directory(_p, children) = parent {
-                        Self::navigate_nodes(
-                            children,
-                            forward,
-                            self.filter.as_ref(),
-                            &mut self.selected_path,
-                            &mut self.selected_file,
-                        );
-                        return;
-                    }
-                }
-            }
+    fn navigate_folder(&self, forward: bool) -> Option<PathBuf> {
+        let selected = self.find_node(self.selected_file.as_ref()?)?.id();
+        let nodes: Vec<_> = self.siblings(selected).iter().map(|&id| (0, self.tree.node(id))).collect();
+        let current = nodes.iter().position(|(_, n)| n.id() == selected)?;
+        // Navigating within a folder always wraps around
+        Self::navigate_nodes(&nodes, Some(current), forward, NavigationWrap::WithinRoot, |n| self.can_navigate_to(n))
+    }
+
+    fn navigate_tree(&self, forward: bool, wrap: NavigationWrap) -> Option<PathBuf> {
+        // Every node in display order, with the index of the root it is under
+        let nodes: Vec<_> = self.tree.roots()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &root)| Nodes::new([self.tree.node(root)], false).map(move |v| (i, v.node)))
+            .collect();
+
+        let selected = self.selected_file.as_ref().and_then(|selected| self.find_node(selected));
+        let current = selected.and_then(|selected| nodes.iter().position(|(_, n)| *n == selected));
+        Self::navigate_nodes(&nodes, current, forward, wrap, |n| self.can_navigate_to(n))
+    }
+
+    fn navigate(&mut self, forward: bool) {
+        let path = match self.navigation {
+            NavigationMode::Folder => self.navigate_folder(forward),
+            NavigationMode::DepthFirst(wrap) => self.navigate_tree(forward, wrap),
+        };
+        if let Some(path) = path {
+            self.select_entry(path);
+        }
+    }
+
+    /// Move the selection to `path` as if it was clicked in the popups.
+    ///
+    /// In files-only mode, or if the selectable predicate rejects it, a directory is only opened,
+    /// keeping the selected file.
+    fn select_entry(&mut self, path: PathBuf) {
+        if (!self.select_files_only || !self.find_node(&path).is_some_and(|n| n.is_dir()))
+            && self.unselectable_reason(&path).is_none()
+        {
+            self.selected_file = Some(path.clone());
+        }
+        self.selected_path = Some(path);
+    }
+
+    /// Select the directory containing the current entry, unless the entry is a root.
+    ///
+    /// The current entry is the one last clicked in the popups or selected from code,
+    /// which may be an opened directory in files-only mode.
+    pub fn select_parent(&mut self) {
+        let Some(parent) = self.selected_path.as_ref().and_then(|p| p.parent()) else {
+            return;
+        };
+        if let Some(node) = self.find_node(parent)
+            && self.can_enter(node)
+        {
+            self.select_entry(parent.to_path_buf());
+        }
+    }
+
+    /// If the current entry is a directory, select its first visible, selectable entry.
+    pub fn select_first_child(&mut self) {
+        let Some(node) = self.selected_path.as_ref().and_then(|p| self.find_node(p)) else {
+            return;
+        };
+        let child = node.children().find(|&c| self.can_enter(c)).map(|c| c.path());
+        if let Some(child) = child {
+            self.select_entry(child);
+        }
+    }
+
+    fn navigate_siblings(&mut self, forward: bool) {
+        let Some(current) = self.selected_path.as_ref().and_then(|p| self.find_node(p)) else {
+            return;
+        };
+        let nodes: Vec<_> = self.siblings(current.id()).iter().map(|&id| (0, self.tree.node(id))).collect();
+        let current = nodes.iter().position(|(_, n)| *n == current);
+        if let Some(path) = Self::navigate_nodes(&nodes, current, forward, NavigationWrap::Stop, |n| self.can_enter(n)) {
+            self.select_entry(path);
+        }
+    }
+
+    /// Select the next visible, selectable entry in the directory of the current entry, file or directory.
+    /// Stops at the last entry.
+    pub fn select_next_sibling(&mut self) {
+        self.navigate_siblings(true);
+    }
+
+    /// Select the previous visible, selectable entry in the directory of the current entry, see `select_next_sibling`.
+    pub fn select_previous_sibling(&mut self) {
+        self.navigate_siblings(false);
+    }
+
+    /// Open the popups of the directory at `path` and its ancestors, without changing the selected file.
+    ///
+    /// The chain is shown the next time the combo box is open.
+    pub fn open_directory<P: AsRef<Path>>(&mut self, path: P) {
+        let Some(path) = self.resolve_path(path.as_ref()) else {
+            return;
+        };
+        if self.find_node(&path).is_some_and(|n| n.is_dir()) {
+            self.selected_path = Some(path);
         }
     }
 
-    /// If a file is selected, select the next file in the parent directory, if one exists.
+    /// Select the next visible, selectable entry, in the parent directory or the whole tree depending on
+    /// `navigation`. Directories are included unless `select_files_only` is set.
     pub fn select_next_file(&mut self) {
-        self.navigate_folder(true);
+        self.navigate(true);
     }
 
-    /// If a file is selected, select the previous file in the parent directory, if one exists.
+    /// Select the previous visible, selectable entry, see `select_next_file`.
     pub fn select_previous_file(&mut self) {
-        self.navigate_folder(false);
+        self.navigate(false);
+    }
+
+    /// Open the combo box the next time it is shown.
+    pub fn open(&mut self) {
+        self.popup_request = Some(true);
+    }
+
+    /// Open the combo box the next time it is shown, with the popups of `path` and its ancestors open.
+    ///
+    /// Like `open_directory`, this doesn't change the selected file. If `path` is a file,
+    /// it is highlighted in the popup of its directory.
+    pub fn open_at<P: AsRef<Path>>(&mut self, path: P) {
+        if let Some(path) = self.resolve_path(path.as_ref())
+            && self.contains_path(&path)
+        {
+            self.selected_path = Some(path);
+        }
+        self.open();
+    }
+
+    /// Close the combo box and all of its popups the next time it is shown.
+    pub fn close(&mut self) {
+        self.popup_request = Some(false);
+    }
+
+    /// Whether the popup of the combo box is open.
+    pub fn is_open(&self, ctx: &egui::Context) -> bool {
+        egui::Popup::is_id_open(ctx, self.popup_id())
+    }
+
+    /// The id the root popup stores its open state under.
+    fn popup_id(&self) -> egui::Id {
+        self.id.with("popup")
     }
 
     /// Set the selected path to `path`.
-    /// 
+    ///
     /// If `select_files_only` is true, `path` must be a file.
-    /// 
-    /// Setting `path` to `None` will clear the selection.
+    ///
+    /// In save mode, `path` may be a file that doesn't exist yet, in an existing directory.
+    ///
+    /// Setting `path` to `None` will clear the selection, unless the combo box is in required mode.
     pub fn set_selection<P: AsRef<Path>>(&mut self, path: Option<P>) {
         match path {
             Some(p) => {
-                let p = match canonicalize(p.as_ref()).ok() {
+                let p = match self.resolve_path(p.as_ref()) {
                     Some(p) => p,
+                    None if self.save_mode => {
+                        // Only the directory of a new file has to exist
+                        let (Some(parent), Some(name)) = (p.as_ref().parent(), p.as_ref().file_name()) else {
+                            return;
+                        };
+                        match self.resolve_path(parent) {
+                            Some(parent) => parent.join(name),
+                            None => return,
+                        }
+                    }
                     None => return,
                 };
-                if self.select_files_only {
-                    if p.is_file() {
+                if self.unselectable_reason(&p).is_some() {
+                    return;
+                }
+                let is_directory = is_directory(&self.tree, self.index(), &p);
+                if self.save_mode {
+                    if is_directory == Some(true) {
+                        self.selected_path = Some(p);
+                    } else {
+                        self.selected_path = p.parent().map(|p| p.to_path_buf());
+                        self.save_name = p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                        self.selected_file = Some(p);
+                    }
+                } else if self.select_files_only {
+                    if is_directory == Some(false) {
                         self.selected_path = Some(p.clone());
                         self.selected_file = Some(p);
                     }
-                } else if p.is_file() {
+                } else if is_directory.is_some() {
                     self.selected_path = Some(p.clone());
                     self.selected_file = Some(p);
-                } else if p.is_dir() {
-                    self.selected_path = Some(p);
                 }
             }
+            None if self.required => {}
             None => {
                 self.selected_path = None;
                 self.selected_file = None;
@@ -342,248 +925,1105 @@ impl DirectoryComboBox {
         }
     }
 
-    pub fn get_all_paths(&self) -> Vec<PathBuf> {
-        let mut paths = Vec::new();
-
-        fn collect_paths(node: &DirectoryNode, paths: &mut Vec<PathBuf>, filter: Option<&Arc<dyn Fn(&Path) -> bool>>) {
-            match node {
-                DirectoryNode::File(p) => {
-                    if filter.as_ref().map_or(true, |f| f(p)) {
-                        paths.push(p.clone());
-                    }
-                }
-                DirectoryNode::Directory(_, children) => {
-                    for child in children {
-                        collect_paths(child, paths, filter);
-                    }
-                }
-            }
-        }
-
-        for root in &self.roots {
-            collect_paths(root, &mut paths, self.filter.as_ref());
+    /// Whether the selection is a path in the tree that can be selected.
+    fn has_valid_selection(&self) -> bool {
+        match self.selected() {
+            Some(p) if self.save_mode => self.is_selectable(p.parent().unwrap_or(p)),
+            Some(p) => self.is_selectable(p),
+            None => false,
         }
+    }
 
-        paths
+    /// The first file in the popups that can be selected.
+    fn first_selectable_file(&self) -> Option<PathBuf> {
+        self.iter()
+            .files()
+            .find(|v| self.selectable.as_ref().is_none_or(|s| s(v.node).is_none()))
+            .map(|v| v.node.path())
     }
-}
 
-fn nested_combobox_ui(
-    ui: &mut egui::Ui,
-    nodes: &[DirectoryNode],
-    depth: usize,
-    id: egui::Id,
-    selected_path: &mut Option<PathBuf>,
-    max_height: Option<f32>,
-    max_width: Option<f32>,
-    show_extensions: bool,
-    filter: Option<&Arc<dyn Fn(&Path) -> bool>>,
-    back_button: bool,
-) {
-    if depth == 0 {
-        ui.selectable_value(selected_path, None, "None");
-    } else if back_button {
-        if ui.button(RichText::new("Back").underline()).clicked() {
-            if let Some(selected_path_unwrap) = selected_path {
-                if depth == 1 {
-                    // Go to root
-                    *selected_path = None;
-                } else {
-                    if selected_path_unwrap.is_dir() {
-                        *selected_path = selected_path_unwrap.parent().map(|p| p.to_path_buf());
-                    } else if selected_path_unwrap.is_file() {
-                        // Go up two levels
-                        *selected_path = selected_path_unwrap.parent().and_then(|p| p.parent()).map(|p| p.to_path_buf());
-                    }
-                }
-            } else {
-                *selected_path = None;
+    /// In required mode, select the default selection or the first file if nothing valid is selected.
+    fn ensure_required_selection(&mut self) {
+        if !self.required || self.has_valid_selection() {
+            return;
+        }
+
+        if let Some(default) = self.default_selection.clone() {
+            self.set_selection(Some(default));
+            if self.has_valid_selection() {
+                return;
             }
         }
+        if let Some(first) = self.first_selectable_file() {
+            self.set_selection(Some(first));
+        }
     }
 
-    let mut file_shown = false;
+    fn index(&self) -> &PathIndex {
+        self.index.get_or_init(|| PathIndex::new(&self.tree))
+    }
 
-    for node in nodes {
-        match node {
-            DirectoryNode::File(p) => {
-                let file_name = p.file_name().expect("File name should be a full path").to_string_lossy();
+    /// The entries of the combo box.
+    ///
+    /// Breaking change: this replaces the public `roots` field. The entries are stored in a
+    /// `DirectoryTree`, which rebuilds paths on demand, see `DirectoryTree::to_nodes` to get
+    /// them as `DirectoryNode`s. Change them with `set_tree` or `set_roots`.
+    pub fn tree(&self) -> &Arc<DirectoryTree> {
+        &self.tree
+    }
 
-                if let Some(filter) = filter {
-                    if !filter(p) {
-                        continue;
-                    }
-                }
+    /// Replace the entries with `tree`. The selection is kept, even if it is no longer in the tree.
+    pub fn set_tree(&mut self, tree: impl Into<Arc<DirectoryTree>>) {
+        self.tree = tree.into();
+        self.invalidate_index();
+    }
 
-                let extension = p.extension().and_then(|ext| ext.to_str()).unwrap_or("");
-                let mut file_name_str = file_name.as_ref();
-                if file_name.ends_with(extension) && !show_extensions {
-                    file_name_str = &file_name_str[..file_name_str.len() - extension.len() - 1];
-                }
+    /// Replace the entries with `roots`, see `set_tree`.
+    pub fn set_roots(&mut self, roots: &[DirectoryNode]) {
+        self.set_tree(DirectoryTree::from_nodes(roots));
+    }
 
-                file_shown = true;
-                if ui.selectable_value(selected_path, Some(p.clone()), file_name_str).clicked() {
-                    // TODO: dont close all popups
-                    egui::Popup::close_all(ui.ctx());
-                };
-            }
-            DirectoryNode::Directory(dir_path, children) => {
-                if let Some(selected_path_unwrap) = selected_path {
-                    if selected_path_unwrap.starts_with(dir_path) {
-                        // This directory needs its own combo box as it is
-                        // selected or an ancestor of the selected item
-                        
-                        let right_of_combobox = ui.next_widget_position() + egui::Vec2::new(ui.available_width(), 0.0);
-                        let combobox_rect = egui::Rect::from_min_size(
-                            right_of_combobox,
-                            egui::Vec2::ZERO
-                        );
-                        let mut child_ui = ui.new_child(egui::UiBuilder::new().max_rect(combobox_rect));
-                        nested_combobox_popup_ui(
-                            &mut child_ui,
-                            children,
-                            depth+1,
-                            id.with(dir_path),
-                            selected_path,
-                            max_height,
-                            max_width,
-                            show_extensions,
-                            filter,
-                            back_button
-                        );
-                    }
-                }
+    /// The tree, to change it. Clones of the combo box keep the tree they had.
+    fn tree_mut(&mut self) -> &mut DirectoryTree {
+        self.invalidate_index();
+        Arc::make_mut(&mut self.tree)
+    }
 
-                file_shown = true;
-                ui.selectable_value(
-                    selected_path,
-                    Some(dir_path.clone()),
-                    RichText::new(
-                        dir_path.file_name().expect("Directory name should be a full path").to_string_lossy()
-                    ).strong()
-                );
+    /// Check the entries against the filter again the next time the popups are shown.
+    ///
+    /// Replacing the filter does this already, but a filter that reads changing state has to be
+    /// rechecked after each change.
+    pub fn refilter(&mut self) {
+        self.visible_rows = VisibleRows::default();
+    }
+
+    /// Drop the lookup table of the tree, so it is rebuilt the next time a path is looked up.
+    fn invalidate_index(&mut self) {
+        self.index.take();
+        self.visible_rows = VisibleRows::default();
+    }
+
+    /// The node at `path` in the tree, found without walking it.
+    pub fn find_node<P: AsRef<Path>>(&self, path: P) -> Option<TreeNode<'_>> {
+        let id = self.index().get(&self.tree, path.as_ref())?;
+        Some(self.tree.node(id))
+    }
+
+    /// Whether `path` is one of the nodes in the tree.
+    pub fn contains_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.index().contains(&self.tree, path.as_ref())
+    }
+
+    /// `path` as the tree stores it: as given if it's in the tree, canonicalized otherwise.
+    ///
+    /// Trees built with `from_path_list` needn't exist on disk, so their paths can't be canonicalized.
+    fn resolve_path(&self, path: &Path) -> Option<PathBuf> {
+        if self.contains_path(path) {
+            return Some(path.to_path_buf());
+        }
+        canonicalize(path).ok()
+    }
+
+    /// The paths of every file the filter accepts, see `iter` to walk the tree without collecting it.
+    pub fn get_all_paths(&self) -> Vec<PathBuf> {
+        self.iter().files().map(|v| v.node.path()).collect()
+    }
+
+    /// Rescan the directory at `path` from disk, or its parent directory if `path` is a file.
+    ///
+    /// Entries that no longer exist on disk are removed. Directories that weren't scanned from disk,
+    /// like those of `new_from_path_list`, are left as they are.
+    pub fn refresh_path<P: AsRef<Path>>(&mut self, path: P) {
+        let Some(id) = self.index().get(&self.tree, path.as_ref()).map(|id| refresh_target(&self.tree, id)) else {
+            return;
+        };
+        if self.tree.metadata(id).is_none() {
+            return;
+        }
+        let dir = self.tree.path(id);
+
+        let tree = self.tree_mut();
+        if !tree.rescan(id) {
+            tree.remove(id);
+        }
+        self.previews.retain(|p, _| !p.starts_with(&dir));
+    }
+
+    /// Why the selectable predicate rejects the node at `path`, if it does.
+    fn unselectable_reason(&self, path: &Path) -> Option<String> {
+        let selectable = self.selectable.as_ref()?;
+        self.find_node(path).and_then(|n| selectable(n))
+    }
+
+    /// Whether `path` is in the tree and could be selected from the popups.
+    fn is_selectable(&self, path: &Path) -> bool {
+        let Some(path) = self.resolve_path(path) else {
+            return false;
+        };
+        if self.unselectable_reason(&path).is_some() {
+            return false;
+        }
+
+        if self.save_mode {
+            // Any file name can be saved to, as long as it's in a directory the popups can open
+            let Some(dir) = (if is_directory(&self.tree, self.index(), &path) == Some(true) { Some(path.as_path()) } else { path.parent() }) else {
+                return false;
+            };
+            return save_directory(&self.tree, self.index(), None).as_deref() == Some(dir)
+                || self.find_node(dir).is_some_and(|n| n.is_dir());
+        }
+
+        match self.find_node(&path) {
+            Some(node) if node.is_dir() => !self.select_files_only,
+            Some(_) => self.filter.as_ref().is_none_or(|f| f(&path)),
+            None => false,
+        }
+    }
+
+    /// Select a file dropped onto the closed combo box from outside the app, if it is inside the roots.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context, response: &egui::Response) {
+        let hint_id = self.id.with("drop_rejected");
+
+        if !self.is_open(ctx) && response.contains_pointer() {
+            if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                let stroke = ctx.style().visuals.selection.stroke;
+                ctx.layer_painter(response.layer_id).rect_stroke(
+                    response.rect,
+                    ctx.style().visuals.widgets.hovered.corner_radius,
+                    stroke,
+                    egui::StrokeKind::Outside,
+                );
+            }
+
+            let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone()));
+            if let Some(dropped) = dropped {
+                if self.is_selectable(&dropped) {
+                    self.set_selection(Some(dropped));
+                    ctx.data_mut(|d| d.remove_temp::<f64>(hint_id));
+                } else {
+                    let until = ctx.input(|i| i.time) + 3.0;
+                    ctx.data_mut(|d| d.insert_temp(hint_id, until));
+                }
+            }
+        }
+
+        if let Some(until) = ctx.data(|d| d.get_temp::<f64>(hint_id)) {
+            let remaining = until - ctx.input(|i| i.time);
+            if remaining > 0.0 {
+                response.show_tooltip_text(&self.labels.drop_rejected);
+                ctx.request_repaint_after_secs(remaining as f32);
+            } else {
+                ctx.data_mut(|d| d.remove_temp::<f64>(hint_id));
             }
         }
     }
 
-    if !file_shown {
-        ui.label("Empty");
+    /// Returns the path of a newly created entry, which should be renamed.
+    fn apply_popup_action(&mut self, action: PopupAction) -> Option<PathBuf> {
+        let entry;
+        let result = match action {
+            PopupAction::Pin(path) => {
+                self.add_favorite(path);
+                return None;
+            }
+            PopupAction::Unpin(path) => {
+                self.remove_favorite(path);
+                return None;
+            }
+            PopupAction::Refresh(path) => {
+                self.refresh_path(path);
+                return None;
+            }
+            PopupAction::CreateDirectory(dir) => {
+                let name = file_ops::unique_child_name(&dir, &self.labels.new_folder_name);
+                entry = dir.join(&name);
+                self.create_directory(dir, &name).map(Some)
+            }
+            PopupAction::CreateFile(dir) => {
+                let name = file_ops::unique_child_name(&dir, &self.labels.new_file_name);
+                entry = dir.join(&name);
+                self.create_file(dir, &name).map(Some)
+            }
+            PopupAction::Rename(path, new_name) => {
+                let result = self.rename_path(&path, &new_name).map(|_| None);
+                entry = path;
+                result
+            }
+            PopupAction::Trash(path) => {
+                let result = self.trash_path(&path).map(|_| None);
+                entry = path;
+                result
+            }
+        };
+
+        match result {
+            Ok(created) => {
+                self.file_operation_error = None;
+                created
+            }
+            Err(e) => {
+                self.file_operation_error = Some(file_ops::describe_error(&self.labels, &e, &entry));
+                None
+            }
+        }
+    }
+}
+
+/// A change requested from inside the popups, applied once they have been drawn.
+enum PopupAction {
+    Pin(PathBuf),
+    Unpin(PathBuf),
+    Refresh(PathBuf),
+    /// Create a new folder inside the directory.
+    CreateDirectory(PathBuf),
+    /// Create a new empty file inside the directory.
+    CreateFile(PathBuf),
+    Rename(PathBuf, String),
+    Trash(PathBuf),
+}
+
+/// The entry a context menu was opened on, kept in egui's temp data while the menu is open.
+///
+/// egui only allows one memory-tracked popup at a time, so the context menu can't use
+/// `Response::context_menu` without closing the combo box.
+#[derive(Clone, Default)]
+struct ContextMenuTarget {
+    path: PathBuf,
+    pos: egui::Pos2,
+    /// Opened on the placeholder of an empty directory, so `path` is where new entries are created.
+    empty_directory: bool,
+    /// "Delete" was clicked, and the menu is asking for confirmation.
+    confirm_delete: bool,
+}
+
+/// The entry being renamed inline, kept in egui's temp data while its text field is shown.
+#[derive(Clone, Default)]
+struct RenameTarget {
+    path: PathBuf,
+    text: String,
+    focused: bool,
+}
+
+/// The file name field shown in save mode.
+struct SaveState<'a> {
+    name: &'a mut String,
+    extension: Option<&'a str>,
+    /// The existing file the user was asked about replacing.
+    confirm_overwrite: &'a mut Option<PathBuf>,
+    /// The path chosen this frame.
+    saved: Option<PathBuf>,
+}
+
+/// The entries each directory shows, so the popups don't filter them every frame.
+#[derive(Clone, Default)]
+struct VisibleRows {
+    /// The address of the filter the entries were checked against.
+    filter: Option<usize>,
+    /// Keyed by the directory, or `None` for the roots.
+    rows: HashMap<Option<NodeId>, Arc<[NodeId]>>,
+}
+
+impl VisibleRows {
+    fn get(&mut self, tree: &DirectoryTree, directory: Option<NodeId>, filter: Option<&PathFilter>) -> Arc<[NodeId]> {
+        // The filter is a public field, so a new one is only noticed here
+        let filter_address = filter.map(|f| Arc::as_ptr(f) as *const () as usize);
+        if self.filter != filter_address {
+            self.filter = filter_address;
+            self.rows.clear();
+        }
+
+        if let Some(rows) = self.rows.get(&directory) {
+            return rows.clone();
+        }
+        let nodes = match directory {
+            Some(directory) => tree.children(directory),
+            None => tree.roots(),
+        };
+        let rows: Arc<[NodeId]> = nodes.iter()
+            .copied()
+            .filter(|&id| tree.is_dir(id) || filter.is_none_or(|f| f(&tree.path(id))))
+            .collect();
+        self.rows.insert(directory, rows.clone());
+        rows
     }
 }
 
-fn nested_combobox_popup_ui(
-    ui: &mut egui::Ui,
-    nodes: &[DirectoryNode],
-    depth: usize,
-    id: egui::Id,
-    selected_path: &mut Option<PathBuf>,
-    max_height: Option<f32>,
-    max_width: Option<f32>,
+/// Settings and state shared by every nested popup while the combo box is shown.
+struct NestedComboBoxState<'a> {
+    tree: &'a DirectoryTree,
+    index: &'a PathIndex,
+    selected_path: &'a mut Option<PathBuf>,
     show_extensions: bool,
-    filter: Option<&Arc<dyn Fn(&Path) -> bool>>,
+    filter: Option<&'a PathFilter>,
+    visible_rows: &'a mut VisibleRows,
+    selectable: Option<&'a SelectablePredicate>,
+    labels: &'a DirectoryComboBoxLabels,
+    required: bool,
     back_button: bool,
-) {
-    let mut popup = egui::Popup::new(
+    layout: PopupLayout,
+    favorites: &'a [PathBuf],
+    context_menu_actions: &'a [ContextMenuAction],
+    file_operations: bool,
+    file_operation_error: Option<&'a str>,
+    renaming: Option<RenameTarget>,
+    save: Option<SaveState<'a>>,
+    drag_and_drop: bool,
+    /// Present if hover previews are enabled.
+    previews: Option<&'a mut HashMap<PathBuf, FilePreview>>,
+    actions: Vec<PopupAction>,
+    /// The id the context menu stores its target under.
+    context_menu_id: egui::Id,
+    popups: PopupState,
+}
+
+/// The node `refresh_path` rescans for the node `id`: `id` if it is a directory, else its parent.
+///
+/// Rescanning the whole parent of a file picks up new and removed siblings.
+fn refresh_target(tree: &DirectoryTree, id: NodeId) -> NodeId {
+    if tree.is_dir(id) { id } else { tree.parent(id).unwrap_or(id) }
+}
+
+/// `path` relative to the directory containing the root it belongs to.
+fn path_relative_to_roots<'a>(tree: &DirectoryTree, path: &'a Path) -> Option<&'a Path> {
+    let root = tree.roots().iter().map(|&r| Path::new(tree.name(r))).find(|r| path.starts_with(r))?;
+    path.strip_prefix(root.parent()?).ok()
+}
+
+/// The last component of `path`, or all of it if it has none, like a root listed as `..` or `/`.
+pub(crate) fn display_name(path: &Path) -> Cow<'_, str> {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()
+}
+
+fn entry_name(path: &Path, show_extensions: bool) -> String {
+    let file_name = display_name(path);
+
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if !show_extensions && !extension.is_empty() && file_name.ends_with(extension) {
+        file_name[..file_name.len() - extension.len() - 1].to_string()
+    } else {
+        file_name.into_owned()
+    }
+}
+
+/// Let the file at `path` be dragged out of the popups onto other widgets.
+fn entry_drag_source(ui: &egui::Ui, response: &egui::Response, path: &Path, state: &NestedComboBoxState) {
+    if !state.drag_and_drop {
+        return;
+    }
+
+    let response = response.interact(egui::Sense::drag());
+    response.dnd_set_drag_payload(path.to_path_buf());
+
+    if response.dragged()
+        && let Some(pos) = ui.ctx().pointer_interact_pos()
+    {
+        egui::Area::new(response.id.with("drag"))
+            .order(egui::Order::Tooltip)
+            .fixed_pos(pos + egui::vec2(12.0, 0.0))
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(entry_name(path, state.show_extensions));
+                });
+            });
+    }
+}
+
+/// Open the context menu for `path` if `response` was secondary clicked.
+fn entry_context_menu(response: &egui::Response, path: &Path, id: egui::Id, state: &mut NestedComboBoxState) {
+    if response.clicked() || response.secondary_clicked() {
+        state.popups.interacted = true;
+    }
+
+    if response.secondary_clicked() {
+        let pos = response.interact_pointer_pos().unwrap_or(response.rect.left_bottom());
+        response.ctx.data_mut(|d| d.insert_temp(id, ContextMenuTarget { path: path.to_path_buf(), pos, ..Default::default() }));
+    }
+}
+
+fn context_menu_ui(ctx: &egui::Context, id: egui::Id, state: &mut NestedComboBoxState) {
+    let Some(target) = ctx.data(|d| d.get_temp::<ContextMenuTarget>(id)) else {
+        return;
+    };
+
+    let response = egui::Popup::new(
         id,
-        ui.ctx().clone(),
-        egui::PopupAnchor::Position(ui.next_widget_position()),
+        ctx.clone(),
+        egui::PopupAnchor::Position(target.pos),
         egui::LayerId::new(egui::Order::Foreground, id.with("popup_layer"))
     )
     .close_behavior(egui::PopupCloseBehavior::IgnoreClicks)
-    .sense(egui::Sense::click())
     .layout(egui::Layout::top_down_justified(egui::Align::LEFT))
     .gap(0.0)
-    .kind(egui::PopupKind::Menu);
+    .kind(egui::PopupKind::Menu)
+    .show(|ui| {
+        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+        if target.confirm_delete {
+            return delete_confirmation_ui(ui, &target, state);
+        }
+        if target.empty_directory {
+            return create_entry_ui(ui, &target.path, state);
+        }
+
+        let mut close = false;
+        if ui.button(&state.labels.copy_path).clicked() {
+            ui.ctx().copy_text(target.path.display().to_string());
+            close = true;
+        }
+        if let Some(relative) = path_relative_to_roots(state.tree, &target.path)
+            && ui.button(&state.labels.copy_relative_path).clicked()
+        {
+            ui.ctx().copy_text(relative.display().to_string());
+            close = true;
+        }
 
-    if let Some(max_width) = max_width {
-        popup = popup.width(max_width);
+        ui.separator();
+
+        if state.favorites.contains(&target.path) {
+            if ui.button(&state.labels.unpin).clicked() {
+                state.actions.push(PopupAction::Unpin(target.path.clone()));
+                close = true;
+            }
+        } else if ui.button(&state.labels.pin).clicked() {
+            state.actions.push(PopupAction::Pin(target.path.clone()));
+            close = true;
+        }
+        // Nodes that weren't scanned from disk, like those of `new_from_path_list`, may not match it,
+        // so they can't be rescanned or changed on disk
+        let tree = state.tree;
+        let node = state.index.get(tree, &target.path);
+        let scanned = |id: NodeId| tree.metadata(id).is_some();
+        if node.is_some_and(|id| scanned(refresh_target(tree, id)))
+            && ui.button(&state.labels.refresh_folder).clicked()
+        {
+            state.actions.push(PopupAction::Refresh(target.path.clone()));
+            close = true;
+        }
+
+        // The roots are in a directory outside the tree, which mustn't be written to or removed from
+        if state.file_operations
+            && node.is_some_and(scanned)
+            && let Some(dir) = target.path.parent()
+                .filter(|p| state.index.get(tree, p).is_some_and(|id| tree.is_dir(id) && scanned(id)))
+        {
+            ui.separator();
+            close |= create_entry_ui(ui, dir, state);
+            if ui.button(&state.labels.rename).clicked() {
+                let text = target.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                state.renaming = Some(RenameTarget { path: target.path.clone(), text, focused: false });
+                close = true;
+            }
+            if ui.button(&state.labels.delete).clicked() {
+                ui.ctx().data_mut(|d| d.insert_temp(id, ContextMenuTarget { confirm_delete: true, ..target.clone() }));
+            }
+        }
+
+        if !state.context_menu_actions.is_empty()
+            && let Some(id) = state.index.get(state.tree, &target.path)
+        {
+            ui.separator();
+            for action in state.context_menu_actions {
+                if ui.button(&action.label).clicked() {
+                    (action.action)(&state.tree.node(id).to_node());
+                    close = true;
+                }
+            }
+        }
+        close
+    });
+
+    let Some(response) = response else {
+        return;
+    };
+
+    let clicked_menu = ctx.input(|i| i.pointer.any_click())
+        && ctx.pointer_interact_pos().is_some_and(|p| response.response.rect.contains(p));
+    if clicked_menu {
+        state.popups.interacted = true;
     }
 
-    popup.show(|ui| {
+    let clicked_elsewhere = ctx.input(|i| i.pointer.any_click()) && !state.popups.interacted;
+    if response.inner || clicked_elsewhere || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        ctx.data_mut(|d| d.remove_temp::<ContextMenuTarget>(id));
+    }
+}
 
-        let mut scroll = egui::ScrollArea::vertical();
+/// The "New folder" and "New file" entries of the context menu. Returns true if one was clicked.
+fn create_entry_ui(ui: &mut egui::Ui, dir: &Path, state: &mut NestedComboBoxState) -> bool {
+    let mut clicked = false;
+    if ui.button(&state.labels.new_folder).clicked() {
+        state.actions.push(PopupAction::CreateDirectory(dir.to_path_buf()));
+        clicked = true;
+    }
+    if ui.button(&state.labels.new_file).clicked() {
+        state.actions.push(PopupAction::CreateFile(dir.to_path_buf()));
+        clicked = true;
+    }
+    clicked
+}
 
-        if let Some(max_height) = max_height {
-            scroll = scroll.max_height(max_height)
-        };
-        
-        scroll.show(ui, |ui| {
-            // Make selectable buttons extend the width of the popup
-            ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
-            nested_combobox_ui(ui, nodes, depth, id, selected_path, max_height, max_width, show_extensions, filter, back_button);
-        })
+/// Replaces the context menu after "Delete" is clicked. Returns true once a choice was made.
+fn delete_confirmation_ui(ui: &mut egui::Ui, target: &ContextMenuTarget, state: &mut NestedComboBoxState) -> bool {
+    let name = target.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    ui.label(state.labels.trash_confirmation.replace("{name}", &name));
+
+    let mut close = false;
+    ui.horizontal(|ui| {
+        if ui.button(&state.labels.move_to_trash).clicked() {
+            state.actions.push(PopupAction::Trash(target.path.clone()));
+            close = true;
+        }
+        if ui.button(&state.labels.cancel).clicked() {
+            close = true;
+        }
     });
+    close
 }
 
-impl egui::Widget for &mut DirectoryComboBox {
-    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        let old_value = self.selected_path.clone();
-        let mut cb = egui::ComboBox::from_id_salt(self.id);
+/// Show the inline rename text field in place of the entry at `path`, if it is being renamed.
+///
+/// Returns false if the entry isn't being renamed and should be drawn normally.
+fn rename_ui(ui: &mut egui::Ui, path: &Path, state: &mut NestedComboBoxState) -> bool {
+    let Some(renaming) = state.renaming.as_mut().filter(|r| r.path == path) else {
+        return false;
+    };
+
+    // Keep the row height, which the popups rely on to lay out only the rows in view
+    let row_height = ui.spacing().interact_size.y;
+    let margin_y = ((row_height - ui.text_style_height(&egui::TextStyle::Body)) / 2.0).max(0.0);
+    let text_edit = egui::TextEdit::singleline(&mut renaming.text)
+        .font(egui::TextStyle::Body)
+        .margin(egui::vec2(4.0, margin_y));
+    let response = ui.add_sized([ui.available_width(), row_height], text_edit);
+    if !renaming.focused {
+        response.request_focus();
+        renaming.focused = true;
+    }
+    if response.clicked() {
+        state.popups.interacted = true;
+    }
 
-        if let Some(max_height) = self.max_height {
-            cb = cb.height(max_height);
+    if response.lost_focus() {
+        if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            state.actions.push(PopupAction::Rename(path.to_path_buf(), renaming.text.clone()));
         }
+        state.renaming = None;
+    }
+    true
+}
 
-        if let Some(max_width) = self.max_width {
-            cb = cb.width(max_width);
+/// Whether `path` is a directory, going by the tree if it's in it and by the disk otherwise.
+/// `None` if it is in neither.
+fn is_directory(tree: &DirectoryTree, index: &PathIndex, path: &Path) -> Option<bool> {
+    match index.get(tree, path) {
+        Some(id) => Some(tree.is_dir(id)),
+        None if path.is_dir() => Some(true),
+        None => path.is_file().then_some(false),
+    }
+}
+
+/// The directory a new file is saved in: the directory open in the deepest popup.
+fn save_directory(tree: &DirectoryTree, index: &PathIndex, selected_path: Option<&Path>) -> Option<PathBuf> {
+    match selected_path {
+        Some(p) => match index.get(tree, p) {
+            Some(id) if tree.is_dir(id) => Some(p.to_path_buf()),
+            _ => p.parent().map(|p| p.to_path_buf()),
+        },
+        // Nothing is open, so save next to the roots if they share a directory
+        None => {
+            let mut roots = tree.roots().iter().map(|&r| Path::new(tree.name(r)));
+            let parent = roots.next()?.parent()?;
+            roots.all(|r| r.parent() == Some(parent)).then(|| parent.to_path_buf())
         }
+    }
+}
+
+fn save_ui(ui: &mut egui::Ui, state: &mut NestedComboBoxState) {
+    let directory = save_directory(state.tree, state.index, state.selected_path.as_deref());
+    let Some(save) = state.save.as_mut() else {
+        return;
+    };
+
+    let name = save.name.trim();
+    let path = directory.as_ref()
+        .filter(|_| file_ops::validate_name(name).is_ok())
+        .map(|dir| match save.extension {
+            Some(ext) if !name.to_lowercase().ends_with(&format!(".{}", ext.to_lowercase())) => dir.join(format!("{name}.{ext}")),
+            _ => dir.join(name),
+        });
+
+    if save.confirm_overwrite.is_some() && *save.confirm_overwrite != path {
+        *save.confirm_overwrite = None;
+    }
 
-        if let Some(wrap_mode) = self.wrap_mode {
-            cb = cb.wrap_mode(wrap_mode);
+    if let Some(directory) = &directory {
+        ui.label(RichText::new(directory.display().to_string()).weak());
+    }
+
+    let mut submit = false;
+    ui.horizontal(|ui| {
+        let response = ui.add(egui::TextEdit::singleline(save.name).hint_text(&state.labels.file_name_hint));
+        if response.clicked() {
+            state.popups.interacted = true;
+        }
+        submit = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        submit |= ui.add_enabled(path.is_some(), egui::Button::new(&state.labels.save)).clicked();
+    });
+
+    if let Some(overwrite) = save.confirm_overwrite.clone() {
+        let name = overwrite.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        ui.colored_label(ui.visuals().warn_fg_color, state.labels.overwrite_warning.replace("{name}", &name));
+        ui.horizontal(|ui| {
+            if ui.button(&state.labels.replace).clicked() {
+                save.saved = Some(overwrite);
+                egui::Popup::close_all(ui.ctx());
+            }
+            if ui.button(&state.labels.cancel).clicked() {
+                state.popups.interacted = true;
+                *save.confirm_overwrite = None;
+            }
+        });
+    } else if submit && let Some(path) = path {
+        if path.exists() {
+            state.popups.interacted = true;
+            *save.confirm_overwrite = Some(path);
+        } else {
+            save.saved = Some(path);
+            egui::Popup::close_all(ui.ctx());
         }
+    }
+
+    ui.separator();
+}
+
+/// Show a file the selectable predicate rejects greyed out, with the reason as a tooltip.
+///
+/// Returns `None` if the file can be selected and should be drawn normally. Otherwise, returns
+/// a response over the row that only reacts to right clicks, for the context menu.
+fn unselectable_file_ui(ui: &mut egui::Ui, node: TreeNode, path: &Path, text: impl Into<egui::WidgetText>, state: &NestedComboBoxState) -> Option<egui::Response> {
+    let reason = state.selectable.and_then(|s| s(node))?;
+
+    let is_selected = state.selected_path.as_deref() == Some(path);
+    let response = ui.add_enabled(false, egui::Button::selectable(is_selected, text.into()));
+    // Disabled widgets don't sense clicks, so the context menu needs its own interaction
+    let response = ui.interact(response.rect, response.id.with("locked"), egui::Sense::click());
+    Some(response.on_hover_text(reason))
+}
+
+/// The text of a directory row, greyed out if the selectable predicate rejects the directory.
+///
+/// Such a directory can still be opened to reach the entries inside, so its row stays clickable.
+fn directory_row_text(node: TreeNode, text: RichText, state: &NestedComboBoxState) -> (RichText, Option<String>) {
+    match state.selectable.and_then(|s| s(node)) {
+        Some(reason) => (text.weak(), Some(reason)),
+        None => (text, None),
+    }
+}
 
-        let selected_text_path = if self.select_files_only {
-            self.selected_file.as_ref()
+fn favorites_ui(ui: &mut egui::Ui, state: &mut NestedComboBoxState) {
+    let mut favorite_shown = false;
+
+    for favorite in state.favorites {
+        let Some(node) = state.index.get(state.tree, favorite).map(|id| state.tree.node(id)) else {
+            continue;
+        };
+
+        let (text, locked_reason) = if node.is_dir() {
+            directory_row_text(node, state.popups.style.directory_text(entry_name(favorite, true)), state)
         } else {
-            self.selected_path.as_ref()
+            if state.filter.is_some_and(|f| !f(favorite)) {
+                continue;
+            }
+            (state.popups.style.file_text(entry_name(favorite, state.show_extensions)), None)
         };
 
-        let cb_response = cb.close_behavior(egui::PopupCloseBehavior::IgnoreClicks)
-            .selected_text(match selected_text_path {
-                Some(p) => p.file_name().expect("Selected file name should be a full path").to_string_lossy(),
-                None => "Select".into(),
-            })
-            .show_ui(ui, |ui| {
-                nested_combobox_ui(
-                    ui,
-                    &self.roots,
-                    0,
-                    self.id.with("child"),
-                    &mut self.selected_path,
-                    self.max_height,
-                    self.max_width,
-                    self.show_extensions,
-                    self.filter.as_ref(),
-                    self.back_button
-                )
-            }).response;
-
-        let popups_clicked = cb_response.clicked() || self.selected_path != old_value;
+        favorite_shown = true;
+        if !node.is_dir()
+            && let Some(response) = unselectable_file_ui(ui, node, favorite, text.clone(), state)
+        {
+            entry_context_menu(&response, favorite, state.context_menu_id, state);
+            continue;
+        }
+        let is_selected = state.selected_path.as_ref() == Some(favorite);
+        let mut response = ui.selectable_label(is_selected, text);
+        if let Some(reason) = locked_reason {
+            response = response.on_hover_text(reason);
+        }
+        if response.clicked() {
+            // Jump the popup chain straight to the favorite
+            *state.selected_path = Some(favorite.clone());
+            if !node.is_dir() && state.save.is_none() {
+                egui::Popup::close_all(ui.ctx());
+            }
+        }
+        if !node.is_dir() {
+            entry_drag_source(ui, &response, favorite, state);
+        }
+        entry_context_menu(&response, favorite, state.context_menu_id, state);
+    }
+
+    if favorite_shown {
+        ui.separator();
+    }
+}
+
+/// The entry of `directory`, or the root if `None`, that is the selected entry or leads to it.
+fn entry_towards_selection(state: &NestedComboBoxState, directory: Option<NodeId>) -> Option<NodeId> {
+    let mut id = state.index.get(state.tree, state.selected_path.as_deref()?)?;
+    while state.tree.parent(id) != directory {
+        id = state.tree.parent(id)?;
+    }
+    Some(id)
+}
+
+/// The rows above the entries: the root popup's error, save field, "None" and favorites, or "Back".
+fn header_ui(ui: &mut egui::Ui, depth: usize, state: &mut NestedComboBoxState) {
+    if depth == 0 {
+        if let Some(error) = state.file_operation_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        save_ui(ui, state);
+        if !state.required && ui.selectable_value(state.selected_path, None, &state.labels.none).clicked() {
+            state.popups.interacted = true;
+        }
+        favorites_ui(ui, state);
+    } else if match state.layout {
+        PopupLayout::Cascading => state.back_button,
+        PopupLayout::DrillDown => true,
+        // The columns of the parent directories are still visible
+        PopupLayout::Columns => false,
+    } && ui.button(state.popups.style.back_text(&state.labels.back)).clicked() {
+        state.popups.interacted = true;
+        if let Some(selected_path_unwrap) = state.selected_path {
+            if depth == 1 {
+                // Go to root
+                *state.selected_path = None;
+            } else if is_directory(state.tree, state.index, selected_path_unwrap) == Some(true) {
+                *state.selected_path = selected_path_unwrap.parent().map(|p| p.to_path_buf());
+            } else if is_directory(state.tree, state.index, selected_path_unwrap) == Some(false) {
+                // Go up two levels
+                *state.selected_path = selected_path_unwrap.parent().and_then(|p| p.parent()).map(|p| p.to_path_buf());
+            }
+        } else {
+            *state.selected_path = None;
+        }
+    }
+}
+
+/// Shown in place of the rows of a directory with no visible entries.
+fn empty_directory_ui(ui: &mut egui::Ui, directory: Option<NodeId>, state: &mut NestedComboBoxState) {
+    let response = ui.label(&state.labels.empty);
+    if let Some(directory) = directory
+        && state.file_operations
+        && state.tree.metadata(directory).is_some()
+    {
+        // Entries can't be created from a context menu when there are none, so allow it here
+        let response = response.interact(egui::Sense::click());
+        if response.secondary_clicked() {
+            state.popups.interacted = true;
+            let pos = response.interact_pointer_pos().unwrap_or(response.rect.left_bottom());
+            ui.ctx().data_mut(|d| d.insert_temp(state.context_menu_id, ContextMenuTarget {
+                path: state.tree.path(directory),
+                pos,
+                empty_directory: true,
+                ..Default::default()
+            }));
+        }
+    }
+}
+
+/// Directories are the branches of the popups, and files their leaves.
+impl PopupTree for NestedComboBoxState<'_> {
+    type Node = NodeId;
+
+    fn popup_state(&self) -> &PopupState {
+        &self.popups
+    }
+
+    fn popup_state_mut(&mut self) -> &mut PopupState {
+        &mut self.popups
+    }
+
+    fn rows(&mut self, directory: Option<NodeId>) -> Arc<[NodeId]> {
+        self.visible_rows.get(self.tree, directory, self.filter)
+    }
+
+    fn open_row(&self, rows: &[NodeId], directory: Option<NodeId>, _depth: usize) -> Option<usize> {
+        let towards_selection = entry_towards_selection(self, directory)?;
+        rows.iter().position(|&row| row == towards_selection)
+    }
+
+    fn is_branch(&self, node: NodeId) -> bool {
+        self.tree.is_dir(node)
+    }
+
+    fn label(&self, node: NodeId) -> String {
+        entry_name(Path::new(self.tree.name(node)), self.show_extensions || self.tree.is_dir(node))
+    }
+
+    fn is_current(&self, node: NodeId, _depth: usize) -> bool {
+        self.selected_path.as_deref().is_some_and(|p| self.tree.has_path(node, p))
+    }
+
+    fn popup_id(&self, id: egui::Id, directory: NodeId) -> egui::Id {
+        id.with(self.tree.path(directory))
+    }
+
+    fn locked_reason(&self, node: NodeId) -> Option<String> {
+        self.selectable.and_then(|s| s(self.tree.node(node)))
+    }
+
+    fn header_ui(&mut self, ui: &mut egui::Ui, depth: usize) {
+        header_ui(ui, depth, self);
+    }
+
+    fn empty_ui(&mut self, ui: &mut egui::Ui, directory: Option<NodeId>) {
+        empty_directory_ui(ui, directory, self);
+    }
+
+    fn replace_row_ui(&mut self, ui: &mut egui::Ui, node: NodeId) -> bool {
+        rename_ui(ui, &self.tree.path(node), self)
+    }
+
+    fn clicked(&mut self, ui: &mut egui::Ui, node: NodeId, _depth: usize) {
+        *self.selected_path = Some(self.tree.path(node));
+        if !self.tree.is_dir(node) && self.save.is_none() {
+            egui::Popup::close_all(ui.ctx());
+        }
+    }
+
+    fn row_response(&mut self, ui: &mut egui::Ui, node: NodeId, response: &egui::Response, locked_leaf: bool) {
+        let path = self.tree.path(node);
+        if !self.tree.is_dir(node) && !locked_leaf {
+            if let Some(previews) = self.previews.as_deref_mut() {
+                preview::preview_tooltip(response, &path, self.tree.metadata(node), self.labels, previews);
+            }
+            entry_drag_source(ui, response, &path, self);
+        }
+        entry_context_menu(response, &path, self.context_menu_id, self);
+    }
+}
+
+/// The directory the drill-down popup is showing: the selected directory, or the one containing
+/// the selected file. Also returns its depth, counting the popup listing the roots as 0.
+fn drill_down_directory(
+    tree: &DirectoryTree,
+    index: &PathIndex,
+    selected_path: Option<&Path>,
+) -> Option<(NodeId, usize)> {
+    let node = index.get(tree, selected_path?)?;
+    let directory = if tree.is_dir(node) { node } else { tree.parent(node)? };
+    let depth = std::iter::successors(Some(directory), |&id| tree.parent(id)).count();
+    Some((directory, depth))
+}
+
+/// The contents of the root popup in the drill-down layout.
+fn drill_down_ui(ui: &mut egui::Ui, id: egui::Id, state: &mut NestedComboBoxState) {
+    let Some((directory, depth)) = drill_down_directory(state.tree, state.index, state.selected_path.as_deref()) else {
+        popup::level_ui(ui, state, None, 0, id);
+        return;
+    };
+
+    // The root popup always shows these, whichever directory it is in
+    if let Some(error) = state.file_operation_error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    save_ui(ui, state);
+
+    let path = state.tree.path(directory);
+    let header = path_relative_to_roots(state.tree, &path).unwrap_or(&path);
+    ui.label(RichText::new(header.display().to_string()).weak());
+    ui.separator();
+    popup::level_ui(ui, state, Some(directory), depth, id.with(&path));
+}
+
+/// The contents of the root popup in the columns layout.
+fn columns_ui(ui: &mut egui::Ui, id: egui::Id, state: &mut NestedComboBoxState) {
+    // The roots, then every directory along the selected path
+    let selected = state.selected_path.as_deref().and_then(|p| state.index.get(state.tree, p));
+    let mut columns: Vec<Option<NodeId>> = std::iter::successors(selected, |&id| state.tree.parent(id))
+        .filter(|&id| state.tree.is_dir(id))
+        .map(Some)
+        .chain([None])
+        .collect();
+    columns.reverse();
+
+    let column_width = state.popups.max_width.unwrap_or(COLUMN_WIDTH);
+    // Grow the popup to fit the columns, scrolling once it would cover most of the screen
+    let separator_width = ui.spacing().item_spacing.x * 2.0 + 1.0;
+    let content_width = columns.len() as f32 * (column_width + separator_width);
+    ui.set_min_width(content_width.min(ui.ctx().screen_rect().width() * 0.8));
+    egui::ScrollArea::horizontal()
+        .id_salt(id.with("columns"))
+        .stick_to_right(true)
+        .show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                for (depth, directory) in columns.into_iter().enumerate() {
+                    if depth > 0 {
+                        ui.separator();
+                    }
+                    ui.vertical(|ui| {
+                        ui.set_width(column_width);
+                        ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
+                            let column_id = directory.map_or(id, |d| id.with(state.tree.path(d)));
+                            popup::level_ui(ui, state, directory, depth, column_id);
+                        });
+                    });
+                }
+            });
+        });
+}
+
+impl egui::Widget for &mut DirectoryComboBox {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        self.ensure_required_selection();
+
+        let old_value = self.selected_path.clone();
+        // The selected path may only be a directory opened in the popups
+        let selected_text = match &self.selected_file {
+            Some(p) => display_name(p).into_owned().into(),
+            None => self.labels.select.clone(),
+        };
+
+        let popup_id = self.popup_id();
+        let rename_id = self.id.with("rename");
+        let context_menu_id = self.id.with("context_menu");
+        let mut state = NestedComboBoxState {
+            tree: &self.tree,
+            index: self.index.get_or_init(|| PathIndex::new(&self.tree)),
+            selected_path: &mut self.selected_path,
+            show_extensions: self.show_extensions,
+            filter: self.filter.as_ref(),
+            visible_rows: &mut self.visible_rows,
+            selectable: self.selectable.as_ref(),
+            labels: &self.labels,
+            required: self.required,
+            back_button: self.back_button,
+            layout: self.layout,
+            favorites: &self.favorites,
+            context_menu_actions: &self.context_menu_actions,
+            file_operations: self.file_operations,
+            file_operation_error: self.file_operation_error.as_deref(),
+            renaming: ui.ctx().data(|d| d.get_temp::<RenameTarget>(rename_id)),
+            save: self.save_mode.then_some(SaveState {
+                name: &mut self.save_name,
+                extension: self.save_extension.as_deref(),
+                confirm_overwrite: &mut self.save_overwrite,
+                saved: None,
+            }),
+            drag_and_drop: self.drag_and_drop,
+            previews: self.hover_previews.then_some(&mut self.previews),
+            actions: Vec::new(),
+            context_menu_id,
+            popups: PopupState {
+                style: self.style.clone().unwrap_or_else(|| DirectoryComboBoxStyle::from_visuals(ui.visuals())),
+                max_height: self.max_height,
+                max_width: self.max_width,
+                cascading: self.layout == PopupLayout::Cascading,
+                popup_direction: self.popup_direction,
+                interacted: false,
+            },
+        };
+
+        match self.popup_request.take() {
+            Some(true) => egui::Popup::open_id(ui.ctx(), popup_id),
+            Some(false) => {
+                egui::Popup::close_id(ui.ctx(), popup_id);
+                ui.ctx().data_mut(|d| d.remove_temp::<ContextMenuTarget>(context_menu_id));
+            }
+            None => {}
+        }
+
+        let button = popup::ComboButton {
+            selected_text,
+            width: self.max_width,
+            wrap_mode: self.wrap_mode,
+        };
+        let style = state.popups.style.clone();
+        let cb_response = popup::combo_box(ui, popup_id, button, &style, |ui| {
+            match self.layout {
+                PopupLayout::Cascading => popup::level_ui(ui, &mut state, None, 0, self.id.with("child")),
+                PopupLayout::DrillDown => drill_down_ui(ui, self.id.with("child"), &mut state),
+                PopupLayout::Columns => columns_ui(ui, self.id.with("child"), &mut state),
+            }
+            context_menu_ui(ui.ctx(), context_menu_id, &mut state);
+        }).response;
+
+        let NestedComboBoxState { actions, popups: PopupState { interacted, .. }, renaming, save, .. } = state;
+        let saved = save.and_then(|s| s.saved);
+
+        ui.ctx().data_mut(|d| match renaming {
+            Some(renaming) => d.insert_temp(rename_id, renaming),
+            None => {
+                d.remove_temp::<RenameTarget>(rename_id);
+            }
+        });
+
+        let popups_clicked = cb_response.clicked() || interacted || self.selected_path != old_value;
         // There was a click and no popups were clicked -> close all popups
         if ui.ctx().input(|i| i.pointer.any_click()) && !popups_clicked {
-            // ID of the root popup, a bit hacky
-            let id_salt = egui::Id::new(self.id);
-            let button_id = ui.make_persistent_id(id_salt);
-            let popup_id = button_id.with("popup");
             egui::Popup::close_id(ui.ctx(), popup_id);
+            ui.ctx().data_mut(|d| d.remove_temp::<ContextMenuTarget>(context_menu_id));
+        }
+
+        if self.drag_and_drop {
+            self.handle_dropped_files(ui.ctx(), &cb_response);
         }
 
-        // If select_files_only is true, only set selected_file if a file is selected
-        // Else, set selected_file to the selected_path
-        if self.selected_path != old_value {
+        for action in actions {
+            if let Some(created) = self.apply_popup_action(action) {
+                // Let the user name the new entry straight away
+                let text = created.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                ui.ctx().data_mut(|d| d.insert_temp(rename_id, RenameTarget { path: created, text, focused: false }));
+            }
+        }
+
+        if self.save_mode {
+            if saved.is_some() {
+                self.selected_file = saved;
+            }
+
+            // Clicking an existing file fills in its name, rather than selecting it
+            if self.selected_path != old_value
+                && let Some(selected_path) = &self.selected_path
+                && is_directory(&self.tree, self.index(), selected_path) == Some(false)
+            {
+                self.save_name = selected_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                self.selected_path = selected_path.parent().map(|p| p.to_path_buf());
+            }
+        } else if self.selected_path != old_value {
+            // If select_files_only is true, only set selected_file if a file is selected
+            // Else, set selected_file to the selected_path
+            // In required mode, closing the popups with "Back" keeps the selection
             if self.select_files_only {
                 if let Some(selected_path) = &self.selected_path {
-                    if selected_path.is_file() {
+                    if is_directory(&self.tree, self.index(), selected_path) == Some(false) {
                         self.selected_file = Some(selected_path.clone());
                     }
-                } else {
+                } else if !self.required {
                     self.selected_file = None;
                 }
-            } else {
-                self.selected_file = self.selected_path.clone();
+            } else if let Some(selected_path) = &self.selected_path {
+                // A directory the selectable predicate rejects is only opened
+                if self.unselectable_reason(selected_path).is_none() {
+                    self.selected_file = Some(selected_path.clone());
+                }
+            } else if !self.required {
+                self.selected_file = None;
             }
         }
 
+        if self.required && !self.has_valid_selection() {
+            let visuals = ui.visuals();
+            ui.painter().rect_stroke(
+                cb_response.rect,
+                visuals.widgets.inactive.corner_radius,
+                egui::Stroke::new(1.0, visuals.error_fg_color),
+                egui::StrokeKind::Outside,
+            );
+        }
+
         cb_response
     }
 }
@@ -0,0 +1,89 @@
+//! Building a tree from a flat list of paths, such as a manifest or the output of `git ls-files`.
+
+use std::{collections::HashMap, ffi::{OsStr, OsString}, path::{Component, Path, PathBuf}};
+
+use crate::{DirectoryComboBox, DirectoryNode};
+
+/// A node while the list is read, with a lookup by name so each entry is only added once.
+#[derive(Default)]
+struct PendingNode {
+    path: PathBuf,
+    /// Set for directories named with a trailing separator, which may have no children.
+    is_dir: bool,
+    children: Vec<PendingNode>,
+    by_name: HashMap<OsString, usize>,
+}
+
+impl PendingNode {
+    fn child(&mut self, name: &OsStr, path: PathBuf) -> &mut PendingNode {
+        let i = *self.by_name.entry(name.to_os_string()).or_insert_with(|| {
+            self.children.push(PendingNode { path, ..Default::default() });
+            self.children.len() - 1
+        });
+        &mut self.children[i]
+    }
+
+    fn into_node(self) -> DirectoryNode {
+        if self.is_dir || !self.children.is_empty() {
+            DirectoryNode::Directory(self.path, self.children.into_iter().map(Self::into_node).collect(), None)
+        } else {
+            DirectoryNode::File(self.path, None)
+        }
+    }
+}
+
+fn ends_with_separator(path: &Path) -> bool {
+    path.as_os_str().as_encoded_bytes().last().is_some_and(|&b| std::path::is_separator(b as char))
+}
+
+impl DirectoryNode {
+    /// Build the tree described by `paths`, adding the directories between them, without touching the disk.
+    ///
+    /// The paths are used as given, apart from `.` and `..` being resolved. The first component of each
+    /// path becomes a root, like `src` or `/home`. A path is a directory if another path is inside it
+    /// or it ends with a separator, and a file otherwise. Entries keep the order they first appear in.
+    pub fn from_path_list<P: AsRef<Path>>(paths: &[P]) -> Vec<DirectoryNode> {
+        let mut top = PendingNode::default();
+
+        for path in paths {
+            let path = path.as_ref();
+            let mut base = PathBuf::new();
+            let mut names: Vec<&OsStr> = Vec::new();
+            for component in path.components() {
+                match component {
+                    Component::Prefix(_) | Component::RootDir => base.push(component),
+                    Component::CurDir => {}
+                    Component::ParentDir => {
+                        if names.last().is_some_and(|&n| n != "..") {
+                            names.pop();
+                        } else if base.as_os_str().is_empty() {
+                            // Above a relative path, there's nothing to resolve it against
+                            names.push(OsStr::new(".."));
+                        }
+                    }
+                    Component::Normal(name) => names.push(name),
+                }
+            }
+            let Some((first, rest)) = names.split_first() else {
+                continue;
+            };
+
+            let root = base.join(first);
+            let mut node = top.child(root.as_os_str(), root.clone());
+            for name in rest {
+                let child_path = node.path.join(name);
+                node = node.child(name, child_path);
+            }
+            node.is_dir |= ends_with_separator(path);
+        }
+
+        top.children.into_iter().map(PendingNode::into_node).collect()
+    }
+}
+
+impl DirectoryComboBox {
+    /// The tree described by `paths` will be the entries, see `DirectoryNode::from_path_list`.
+    pub fn new_from_path_list<P: AsRef<Path>>(paths: &[P]) -> Self {
+        Self::new_from_nodes(DirectoryNode::from_path_list(paths))
+    }
+}
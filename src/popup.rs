@@ -0,0 +1,360 @@
+//! The parts of the nested popups that don't depend on what kind of node the rows show.
+
+use std::{ops::Range, sync::Arc};
+
+use egui::scroll_area::ScrollAreaOutput;
+
+use crate::{DirectoryComboBoxStyle, PopupDirection};
+
+/// Settings and state shared by every nested popup while a combo box is shown.
+pub(crate) struct PopupState {
+    pub(crate) style: DirectoryComboBoxStyle,
+    pub(crate) max_height: Option<f32>,
+    pub(crate) max_width: Option<f32>,
+    /// Whether the popup of the branch leading to the entry clicked last opens beside its row.
+    /// Otherwise the branches are shown elsewhere, and every popup lists its rows like the root popup.
+    pub(crate) cascading: bool,
+    /// The side the next nested popup prefers, following the direction of its parent.
+    pub(crate) popup_direction: PopupDirection,
+    /// Whether any entry or menu of the popups was clicked this frame.
+    pub(crate) interacted: bool,
+}
+
+/// The nodes a combo box shows in its nested popups, and what happens when their rows are used.
+pub(crate) trait PopupTree {
+    type Node: Copy;
+
+    fn popup_state(&self) -> &PopupState;
+
+    fn popup_state_mut(&mut self) -> &mut PopupState;
+
+    /// The rows of the popup of `branch`, or of the root popup if `None`.
+    fn rows(&mut self, branch: Option<Self::Node>) -> Arc<[Self::Node]>;
+
+    /// Which of `rows`, listed in the popup of `branch` at `depth`, is the entry clicked last or leads to it.
+    fn open_row(&self, rows: &[Self::Node], branch: Option<Self::Node>, depth: usize) -> Option<usize>;
+
+    fn is_branch(&self, node: Self::Node) -> bool;
+
+    fn label(&self, node: Self::Node) -> String;
+
+    /// Whether `node`, listed at `depth`, is the entry clicked last.
+    fn is_current(&self, node: Self::Node, depth: usize) -> bool;
+
+    /// The id of the popup of the branch `node`, listed in the popup `id`.
+    fn popup_id(&self, id: egui::Id, node: Self::Node) -> egui::Id;
+
+    /// Why `node` can't be selected, shown over its greyed out row. A locked branch can still be opened.
+    fn locked_reason(&self, _node: Self::Node) -> Option<String> {
+        None
+    }
+
+    /// The rows above the entries of the popup at `depth`, like one clearing the selection or going back.
+    fn header_ui(&mut self, ui: &mut egui::Ui, depth: usize);
+
+    /// Shown in the popup of `branch` in place of the rows, if it has none.
+    fn empty_ui(&mut self, ui: &mut egui::Ui, branch: Option<Self::Node>);
+
+    /// Show something else in place of the row of `node`, like a text field to rename it.
+    ///
+    /// Returns false if the row should be drawn normally.
+    fn replace_row_ui(&mut self, _ui: &mut egui::Ui, _node: Self::Node) -> bool {
+        false
+    }
+
+    /// The row of `node`, listed at `depth`, was clicked. Not called for locked leaves.
+    fn clicked(&mut self, ui: &mut egui::Ui, node: Self::Node, depth: usize);
+
+    /// React to anything else done to the row of `node`, like a right click or a drag.
+    ///
+    /// `locked_leaf` is set if `node` is a leaf with a locked reason, whose row only senses clicks.
+    fn row_response(&mut self, _ui: &mut egui::Ui, _node: Self::Node, _response: &egui::Response, _locked_leaf: bool) {}
+}
+
+/// The settings of the button opening the root popup, like those of `egui::ComboBox`.
+pub(crate) struct ComboButton {
+    pub(crate) selected_text: egui::WidgetText,
+    pub(crate) width: Option<f32>,
+    pub(crate) wrap_mode: Option<egui::TextWrapMode>,
+}
+
+/// A button drawn like `egui::ComboBox`, toggling a popup stored under `popup_id`.
+///
+/// `egui::ComboBox` derives the id of its popup from the ui it is in, so it couldn't be
+/// opened or closed from code before the combo box was drawn.
+///
+/// The popup opens `style.popup_gap` away from the button, with `style` applied to its contents.
+pub(crate) fn combo_box<R>(
+    ui: &mut egui::Ui,
+    popup_id: egui::Id,
+    button: ComboButton,
+    style: &DirectoryComboBoxStyle,
+    add_contents: impl FnOnce(&mut egui::Ui) -> R,
+) -> egui::InnerResponse<Option<R>> {
+    let is_open = egui::Popup::is_id_open(ui.ctx(), popup_id);
+    let wrap_mode = button.wrap_mode.unwrap_or_else(|| ui.wrap_mode());
+    let padding = ui.spacing().button_padding;
+    let icon_size = egui::Vec2::splat(ui.spacing().icon_width);
+    let icon_spacing = ui.spacing().icon_spacing;
+
+    let wrap_width = if wrap_mode == egui::TextWrapMode::Extend {
+        f32::INFINITY
+    } else {
+        ui.available_width() - icon_spacing - icon_size.x - 2.0 * padding.x
+    };
+    let galley = button.selected_text.into_galley(ui, Some(wrap_mode), wrap_width, egui::TextStyle::Button);
+
+    let min_width = button.width.unwrap_or_else(|| ui.spacing().combo_width);
+    let size = egui::vec2(
+        (galley.size().x + icon_spacing + icon_size.x + 2.0 * padding.x).max(min_width),
+        (galley.size().y.max(icon_size.y) + 2.0 * padding.y).max(ui.spacing().interact_size.y),
+    );
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::ComboBox, ui.is_enabled(), ""));
+
+    if ui.is_rect_visible(rect) {
+        let visuals = if is_open { &ui.visuals().widgets.open } else { ui.style().interact(&response) };
+        ui.painter().rect(
+            rect.expand(visuals.expansion),
+            visuals.corner_radius,
+            visuals.weak_bg_fill,
+            visuals.bg_stroke,
+            egui::StrokeKind::Inside,
+        );
+
+        let inner = rect.shrink2(padding);
+        // A downward pointing triangle, like the default icon of `egui::ComboBox`
+        let icon_rect = egui::Align2::RIGHT_CENTER.align_size_within_rect(icon_size, inner);
+        let icon = egui::Rect::from_center_size(icon_rect.center(), icon_rect.size() * egui::vec2(0.7, 0.45));
+        ui.painter().add(egui::Shape::convex_polygon(
+            vec![icon.left_top(), icon.right_top(), icon.center_bottom()],
+            visuals.fg_stroke.color,
+            egui::Stroke::NONE,
+        ));
+
+        let text_pos = egui::Align2::LEFT_CENTER.align_size_within_rect(galley.size(), inner).min;
+        ui.painter().galley(text_pos, galley, visuals.text_color());
+    }
+
+    let inner = egui::Popup::menu(&response)
+        .id(popup_id)
+        .width(response.rect.width())
+        .close_behavior(egui::PopupCloseBehavior::IgnoreClicks)
+        .style(egui::style::StyleModifier::default())
+        .gap(style.popup_gap)
+        .show(|ui| {
+            ui.set_min_width(ui.available_width());
+            style.apply_popup_style(ui);
+            // The rows scroll on their own, limited to `max_height`, below the entries above them
+            add_contents(ui)
+        })
+        .map(|r| r.inner);
+
+    egui::InnerResponse { inner, response }
+}
+
+/// The popup of the branch whose row is about to be added to `ui`, and the side it opens on.
+///
+/// Popups nested in it should prefer the returned side, so a flipped chain doesn't zigzag.
+fn branch_popup(
+    ui: &egui::Ui,
+    id: egui::Id,
+    direction: PopupDirection,
+    gap: f32,
+    max_width: Option<f32>,
+) -> (egui::Popup<'static>, PopupDirection) {
+    // The popup opens beside the row, which spans the width of the parent popup
+    let row_rect = egui::Rect::from_min_size(
+        ui.next_widget_position(),
+        egui::vec2(ui.available_width(), ui.spacing().interact_size.y),
+    );
+    let mut popup = egui::Popup::new(
+        id,
+        ui.ctx().clone(),
+        egui::PopupAnchor::ParentRect(row_rect),
+        egui::LayerId::new(egui::Order::Foreground, id.with("popup_layer"))
+    )
+    .align(direction.aligns()[0])
+    .align_alternatives(&direction.aligns()[1..])
+    .close_behavior(egui::PopupCloseBehavior::IgnoreClicks)
+    .sense(egui::Sense::click())
+    .layout(egui::Layout::top_down_justified(egui::Align::LEFT))
+    .gap(gap)
+    .kind(egui::PopupKind::Menu);
+
+    if let Some(max_width) = max_width {
+        popup = popup.width(max_width);
+    }
+
+    let direction = if popup.get_best_align() == egui::RectAlign::LEFT_START {
+        PopupDirection::Left
+    } else {
+        PopupDirection::Right
+    };
+    (popup, direction)
+}
+
+/// Lay out `count` rows in a scroll area, only adding the ones in view.
+///
+/// Each row must be `interact_size.y` high, see `DirectoryComboBoxStyle::apply_popup_style`.
+///
+/// Returns the range of rows that were added.
+fn virtual_rows(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    max_height: Option<f32>,
+    in_root_popup: bool,
+    count: usize,
+    mut row_ui: impl FnMut(&mut egui::Ui, usize),
+) -> ScrollAreaOutput<Range<usize>> {
+    let max_height = max_height.unwrap_or_else(|| if in_root_popup {
+        ui.spacing().combo_height
+    } else {
+        ui.ctx().screen_rect().height()
+    });
+
+    egui::ScrollArea::vertical()
+        .id_salt(id.with("rows"))
+        .max_height(max_height)
+        .show_rows(ui, ui.spacing().interact_size.y, count, |ui, rows| {
+            for row in rows.clone() {
+                row_ui(ui, row);
+            }
+            rows
+        })
+}
+
+/// A ui at the edge of the rows that `row` is scrolled past, or `None` if it is in view.
+///
+/// The popup of an open branch is shown from here, so it stays open while its row is out of view.
+fn scrolled_out_row_ui(ui: &mut egui::Ui, rows: &ScrollAreaOutput<Range<usize>>, row: usize) -> Option<egui::Ui> {
+    if rows.inner.contains(&row) {
+        return None;
+    }
+    let row_height = ui.spacing().interact_size.y;
+    let rect = rows.inner_rect;
+    let top = if row < rows.inner.start { rect.top() } else { rect.bottom() - row_height };
+    let anchor = egui::Rect::from_min_size(egui::pos2(rect.left(), top), egui::vec2(rect.width(), row_height));
+    Some(ui.new_child(egui::UiBuilder::new().max_rect(anchor)))
+}
+
+/// The rows of the popup of `branch` at `depth`, or of the root popup if `None`.
+pub(crate) fn level_ui<T: PopupTree>(
+    ui: &mut egui::Ui,
+    tree: &mut T,
+    branch: Option<T::Node>,
+    depth: usize,
+    id: egui::Id,
+) {
+    tree.header_ui(ui, depth);
+
+    // Only the rows in view are laid out, so work out which nodes are shown up front
+    let rows = tree.rows(branch);
+    if rows.is_empty() {
+        tree.empty_ui(ui, branch);
+        return;
+    }
+
+    // The row leading to the entry clicked last. If it is a branch, its popup is open, and must
+    // stay open when scrolled out of view
+    let open_row = tree.open_row(&rows, branch, depth);
+    let state = tree.popup_state();
+    let cascading = state.cascading;
+    let shown = virtual_rows(ui, id, state.max_height, depth == 0 || !cascading, rows.len(), |ui, row| {
+        entry_row_ui(ui, tree, rows[row], row, depth, id, open_row == Some(row));
+    });
+
+    if cascading
+        && let Some(row) = open_row
+        && tree.is_branch(rows[row])
+        && let Some(mut child_ui) = scrolled_out_row_ui(ui, &shown, row)
+    {
+        let child_id = tree.popup_id(id, rows[row]);
+        branch_popup_ui(&mut child_ui, tree, rows[row], depth + 1, child_id);
+    }
+}
+
+/// The row of a single node, and the popup of a branch if it is open.
+fn entry_row_ui<T: PopupTree>(
+    ui: &mut egui::Ui,
+    tree: &mut T,
+    node: T::Node,
+    row: usize,
+    depth: usize,
+    id: egui::Id,
+    on_open_path: bool,
+) {
+    if depth == 0 && row > 0 && tree.popup_state().style.root_separators {
+        // Painted rather than laid out, so every row keeps the same height
+        let y = ui.next_widget_position().y - ui.spacing().item_spacing.y / 2.0;
+        ui.painter().hline(ui.max_rect().x_range(), y, ui.visuals().widgets.noninteractive.bg_stroke);
+    }
+
+    let is_branch = tree.is_branch(node);
+    if is_branch && on_open_path && tree.popup_state().cascading {
+        let child_id = tree.popup_id(id, node);
+        branch_popup_ui(ui, tree, node, depth + 1, child_id);
+    }
+
+    if tree.replace_row_ui(ui, node) {
+        return;
+    }
+
+    let style = &tree.popup_state().style;
+    let text = if is_branch { style.directory_text(tree.label(node)) } else { style.file_text(tree.label(node)) };
+    let current = tree.is_current(node, depth);
+    let locked_reason = tree.locked_reason(node);
+    let locked_leaf = locked_reason.is_some() && !is_branch;
+
+    let response = if locked_leaf {
+        let response = ui.add_enabled(false, egui::Button::selectable(current, text));
+        // Disabled widgets don't sense clicks, so right clicks need their own interaction
+        ui.interact(response.rect, response.id.with("locked"), egui::Sense::click())
+    } else {
+        let text = if locked_reason.is_some() { text.weak() } else { text };
+        // Highlight the branches leading to the entry clicked last, whose popups are open
+        let background = ui.painter().add(egui::Shape::Noop);
+        let response = ui.selectable_label(current, text);
+        if on_open_path && !current {
+            ui.painter().set(background, egui::Shape::rect_filled(
+                response.rect,
+                ui.visuals().widgets.inactive.corner_radius,
+                tree.popup_state().style.selected_ancestor_fill,
+            ));
+        }
+        response
+    };
+    let response = match locked_reason {
+        Some(reason) => response.on_hover_text(reason),
+        None => response,
+    };
+
+    if response.clicked() || response.secondary_clicked() {
+        tree.popup_state_mut().interacted = true;
+    }
+    if response.clicked() && !locked_leaf {
+        tree.clicked(ui, node, depth);
+    }
+    tree.row_response(ui, node, &response, locked_leaf);
+}
+
+/// Show the popup of the branch whose row is about to be added to `ui`.
+fn branch_popup_ui<T: PopupTree>(
+    ui: &mut egui::Ui,
+    tree: &mut T,
+    branch: T::Node,
+    depth: usize,
+    id: egui::Id,
+) {
+    let state = tree.popup_state();
+    let (popup, direction) = branch_popup(ui, id, state.popup_direction, state.style.popup_gap, state.max_width);
+    // Keep going the way this popup went
+    let parent_direction = std::mem::replace(&mut tree.popup_state_mut().popup_direction, direction);
+
+    popup.show(|ui| {
+        tree.popup_state().style.apply_popup_style(ui);
+        level_ui(ui, tree, Some(branch), depth, id);
+    });
+
+    tree.popup_state_mut().popup_direction = parent_direction;
+}
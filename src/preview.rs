@@ -0,0 +1,129 @@
+//! Tooltips previewing the file under the pointer in the popups.
+
+use std::{collections::HashMap, io::Read, path::{Path, PathBuf}, time::SystemTime};
+
+use crate::{DirectoryComboBoxLabels, NodeMetadata};
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "svg", "ico", "tif", "tiff"];
+const THUMBNAIL_SIZE: f32 = 128.0;
+const TEXT_PREVIEW_BYTES: usize = 4096;
+const TEXT_PREVIEW_LINES: usize = 8;
+
+#[derive(Clone, Debug)]
+enum PreviewContent {
+    Image,
+    Text(String),
+    None,
+}
+
+/// What is shown when hovering a file, loaded the first time it is hovered.
+#[derive(Clone, Debug)]
+pub(crate) struct FilePreview {
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    content: PreviewContent,
+}
+
+impl FilePreview {
+    /// Load the preview of `path`, only reading its metadata from disk if it wasn't cached on the node.
+    pub(crate) fn load(path: &Path, metadata: Option<&NodeMetadata>) -> Self {
+        let metadata = metadata.cloned().unwrap_or_else(|| NodeMetadata::read(path));
+
+        let is_image = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+
+        let content = if is_image {
+            PreviewContent::Image
+        } else {
+            read_text_preview(path).map_or(PreviewContent::None, PreviewContent::Text)
+        };
+
+        Self {
+            size: metadata.size,
+            modified: metadata.modified,
+            content,
+        }
+    }
+
+    pub(crate) fn ui(&self, ui: &mut egui::Ui, path: &Path, labels: &DirectoryComboBoxLabels) {
+        ui.label(path.display().to_string());
+
+        let mut details = Vec::new();
+        if let Some(size) = self.size {
+            details.push(format_size(size));
+        }
+        if let Some(modified) = self.modified {
+            details.push(labels.modified.replace("{time}", &crate::time::format_utc(modified, ' ')));
+        }
+        if !details.is_empty() {
+            ui.label(egui::RichText::new(details.join("  ·  ")).weak());
+        }
+
+        match &self.content {
+            PreviewContent::Image => {
+                ui.add(
+                    egui::Image::from_uri(format!("file://{}", path.display()))
+                        .max_size(egui::Vec2::splat(THUMBNAIL_SIZE))
+                );
+            }
+            PreviewContent::Text(text) if !text.is_empty() => {
+                ui.separator();
+                ui.label(egui::RichText::new(text).monospace());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The first lines of `path`, if it looks like a text file.
+fn read_text_preview(path: &Path) -> Option<String> {
+    let mut buf = Vec::with_capacity(TEXT_PREVIEW_BYTES);
+    std::fs::File::open(path).ok()?
+        .take(TEXT_PREVIEW_BYTES as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    if buf.contains(&0) {
+        return None;
+    }
+
+    // The read may have cut a character in half
+    let text = match std::str::from_utf8(&buf) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&buf[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+
+    Some(text.lines().take(TEXT_PREVIEW_LINES).collect::<Vec<_>>().join("\n"))
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Show the preview tooltip for the file at `path` when `response` is hovered.
+pub(crate) fn preview_tooltip(
+    response: &egui::Response,
+    path: &Path,
+    metadata: Option<&NodeMetadata>,
+    labels: &DirectoryComboBoxLabels,
+    cache: &mut HashMap<PathBuf, FilePreview>,
+) {
+    response.clone().on_hover_ui(|ui| {
+        cache.entry(path.to_path_buf())
+            .or_insert_with(|| FilePreview::load(path, metadata))
+            .ui(ui, path, labels);
+    });
+}
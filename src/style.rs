@@ -0,0 +1,98 @@
+use egui::{Color32, RichText, TextStyle, TextWrapMode, Visuals};
+
+/// How the rows and popups of the combo box look.
+///
+/// A `HierarchyComboBox` draws its branches like directories and its leaves like files. Unless one
+/// is set with `with_style`, it is made from the current `Visuals` every frame, so it follows theme changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectoryComboBoxStyle {
+    pub directory_text_style: Option<TextStyle>,
+    /// The color of directory names, or the widget's text color if `None`.
+    pub directory_color: Option<Color32>,
+    pub directory_strong: bool,
+    pub file_text_style: Option<TextStyle>,
+    /// The color of file names, or the widget's text color if `None`.
+    pub file_color: Option<Color32>,
+    pub back_underline: bool,
+    /// The background of the rows of directories whose popups are open, leading to the selection.
+    pub selected_ancestor_fill: Color32,
+    /// The height of each row, or the egui default if `None`. Rows grow to fit their text style.
+    pub row_height: Option<f32>,
+    /// The space between a popup and the row or combo box it opened from.
+    pub popup_gap: f32,
+    pub popup_wrap_mode: TextWrapMode,
+    /// Whether to draw a separator between the roots in the root popup.
+    pub root_separators: bool,
+}
+
+impl DirectoryComboBoxStyle {
+    pub fn from_visuals(visuals: &Visuals) -> Self {
+        Self {
+            directory_text_style: None,
+            directory_color: None,
+            directory_strong: true,
+            file_text_style: None,
+            file_color: None,
+            back_underline: true,
+            selected_ancestor_fill: visuals.selection.bg_fill.gamma_multiply(0.3),
+            row_height: None,
+            popup_gap: 0.0,
+            popup_wrap_mode: TextWrapMode::Extend,
+            root_separators: false,
+        }
+    }
+
+    pub(crate) fn directory_text(&self, text: impl Into<String>) -> RichText {
+        let mut text = RichText::new(text);
+        if let Some(color) = self.directory_color {
+            text = text.color(color);
+        }
+        if self.directory_strong {
+            text = text.strong();
+        }
+        match &self.directory_text_style {
+            Some(style) => text.text_style(style.clone()),
+            None => text,
+        }
+    }
+
+    pub(crate) fn file_text(&self, text: impl Into<String>) -> RichText {
+        let mut text = RichText::new(text);
+        if let Some(color) = self.file_color {
+            text = text.color(color);
+        }
+        match &self.file_text_style {
+            Some(style) => text.text_style(style.clone()),
+            None => text,
+        }
+    }
+
+    pub(crate) fn back_text(&self, text: &str) -> RichText {
+        let text = RichText::new(text);
+        if self.back_underline { text.underline() } else { text }
+    }
+
+    /// Make `interact_size.y` fit the text of every row, so the rows are all that high.
+    fn apply_row_height(&self, ui: &mut egui::Ui) {
+        let text_height = [&self.directory_text_style, &self.file_text_style]
+            .into_iter()
+            .map(|style| ui.text_style_height(style.as_ref().unwrap_or(&TextStyle::Button)))
+            .fold(ui.text_style_height(&TextStyle::Body), f32::max);
+        let row_height = self.row_height.unwrap_or(ui.spacing().interact_size.y)
+            .max(text_height + 2.0 * ui.spacing().button_padding.y);
+        ui.spacing_mut().interact_size.y = row_height;
+    }
+
+    /// Set up the ui inside a popup.
+    pub(crate) fn apply_popup_style(&self, ui: &mut egui::Ui) {
+        // Make selectable buttons extend the width of the popup
+        ui.style_mut().wrap_mode = Some(self.popup_wrap_mode);
+        self.apply_row_height(ui);
+    }
+}
+
+impl Default for DirectoryComboBoxStyle {
+    fn default() -> Self {
+        Self::from_visuals(&Visuals::default())
+    }
+}
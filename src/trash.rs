@@ -0,0 +1,130 @@
+//! Moving files to the trash, following the freedesktop.org trash specification.
+
+use std::{io, path::Path};
+
+use crate::file_ops::FileOperationError;
+
+#[cfg(unix)]
+pub(crate) fn move_to_trash(path: &Path) -> io::Result<()> {
+    use std::{fs, os::unix::fs::MetadataExt, path::PathBuf};
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or_else(|| FileOperationError::NoTrash(io::ErrorKind::NotFound, "No home directory to put the trash in".to_string()))?;
+
+    let home_trash = data_home.join("Trash");
+    fs::create_dir_all(&home_trash)?;
+
+    // Files can't be renamed across devices, so those on another one go to the trash at the top of their volume
+    let device = fs::symlink_metadata(path)?.dev();
+    if fs::metadata(&home_trash)?.dev() == device {
+        return trash_into(&home_trash, path, path);
+    }
+
+    let parent = path.parent()
+        .ok_or_else(|| FileOperationError::Refused("Path has no parent directory".to_string()))?;
+    let mut top_dir = parent;
+    while let Some(dir) = top_dir.parent()
+        && fs::metadata(dir)?.dev() == device
+    {
+        top_dir = dir;
+    }
+
+    let trash = volume_trash(top_dir)?;
+    // Paths in the trash of a volume are relative to its top directory, so it can be mounted elsewhere
+    trash_into(&trash, path, path.strip_prefix(top_dir).unwrap_or(path))
+}
+
+/// The trash directory of the user on the volume mounted at `top_dir`, created if needed.
+#[cfg(unix)]
+fn volume_trash(top_dir: &Path) -> io::Result<std::path::PathBuf> {
+    use std::{fs, os::unix::fs::{DirBuilderExt, PermissionsExt}};
+
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true).mode(0o700);
+
+    // A shared `.Trash` is only trusted if it is a real directory with the sticky bit set
+    let shared = top_dir.join(".Trash");
+    if let Ok(metadata) = fs::symlink_metadata(&shared)
+        && metadata.is_dir()
+        && metadata.permissions().mode() & 0o1000 != 0
+    {
+        let trash = shared.join(uid.to_string());
+        if builder.create(&trash).is_ok() {
+            return Ok(trash);
+        }
+    }
+
+    let trash = top_dir.join(format!(".Trash-{uid}"));
+    builder.create(&trash).map_err(|e| FileOperationError::NoTrash(
+        e.kind(),
+        format!("Can't create the trash of the volume at {top_dir:?}: {e}"),
+    ))?;
+    Ok(trash)
+}
+
+/// Move `path` into the `files` directory of `trash`, recording `original` as where it came from.
+#[cfg(unix)]
+fn trash_into(trash: &Path, path: &Path, original: &Path) -> io::Result<()> {
+    use std::{ffi::OsString, fs, io::Write, os::unix::ffi::OsStrExt};
+
+    let files_dir = trash.join("files");
+    let info_dir = trash.join("info");
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+
+    let name = path.file_name()
+        .ok_or_else(|| FileOperationError::Refused("Path has no file name".to_string()))?;
+
+    let mut encoded_path = String::new();
+    for &byte in original.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded_path.push(byte as char);
+        } else {
+            encoded_path.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    // The info file is created first and exclusively, reserving the name in the trash
+    for counter in 1.. {
+        let mut trash_name = name.to_os_string();
+        if counter > 1 {
+            trash_name.push(format!(".{counter}"));
+        }
+        let mut info_name = trash_name.clone();
+        info_name.push(".trashinfo");
+
+        let info_path = info_dir.join(&info_name);
+        let mut info_file = match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+
+        let result = write!(
+            info_file,
+            "[Trash Info]\nPath={encoded_path}\nDeletionDate={}\n",
+            // The spec asks for local time, but there is no time zone database to hand
+            crate::time::format_utc(std::time::SystemTime::now(), 'T')
+        ).and_then(|_| fs::rename(path, files_dir.join(OsString::from(&trash_name))));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&info_path);
+        }
+        return result;
+    }
+
+    unreachable!()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(FileOperationError::NoTrash(
+        io::ErrorKind::Unsupported,
+        "Moving to the trash is only supported on freedesktop.org systems".to_string(),
+    ).into())
+}
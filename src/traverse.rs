@@ -0,0 +1,226 @@
+//! Walking the nodes of a tree, with iterators or a visitor.
+
+use std::collections::VecDeque;
+
+use crate::{DirectoryComboBox, DirectoryNode, PathFilter, tree::TreeNode};
+
+/// A node that can be walked: a `&DirectoryNode`, or a `TreeNode` of a `DirectoryTree`.
+pub trait WalkNode: Copy {
+    fn is_dir(self) -> bool;
+
+    /// Whether `filter` lets the node through.
+    fn accepted_by(self, filter: &PathFilter) -> bool;
+
+    fn children(self) -> impl DoubleEndedIterator<Item = Self>;
+}
+
+impl<'a> WalkNode for &'a DirectoryNode {
+    fn is_dir(self) -> bool {
+        matches!(self, DirectoryNode::Directory(..))
+    }
+
+    fn accepted_by(self, filter: &PathFilter) -> bool {
+        filter(self.path())
+    }
+
+    fn children(self) -> impl DoubleEndedIterator<Item = &'a DirectoryNode> {
+        let children: &[DirectoryNode] = match self {
+            DirectoryNode::Directory(_, children, _) => children,
+            DirectoryNode::File(..) => &[],
+        };
+        children.iter()
+    }
+}
+
+impl<'a> WalkNode for TreeNode<'a> {
+    fn is_dir(self) -> bool {
+        TreeNode::is_dir(self)
+    }
+
+    fn accepted_by(self, filter: &PathFilter) -> bool {
+        filter(&self.path())
+    }
+
+    fn children(self) -> impl DoubleEndedIterator<Item = TreeNode<'a>> {
+        TreeNode::children(self)
+    }
+}
+
+/// A node reached while walking a tree, and where it is.
+#[derive(Debug, Clone, Copy)]
+pub struct Visit<N> {
+    pub node: N,
+    /// How many directories are above the node, counting from the node the walk started at.
+    pub depth: usize,
+    /// The directory containing the node, unless the walk started at it.
+    pub parent: Option<N>,
+}
+
+/// What a visitor passed to `visit` wants to happen next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitControl {
+    Continue,
+    /// Don't go into the children of the node just visited.
+    SkipChildren,
+    /// End the walk.
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKinds {
+    All,
+    Files,
+    Directories,
+}
+
+/// An iterator over the nodes of a tree, depth first or breadth first.
+///
+/// Directories are visited before their children in both orders.
+#[derive(Clone)]
+pub struct Nodes<'a, N> {
+    pending: VecDeque<Visit<N>>,
+    breadth_first: bool,
+    /// The last directory returned, whose children are queued when the next node is asked for.
+    last: Option<Visit<N>>,
+    kinds: NodeKinds,
+    filter: Option<&'a PathFilter>,
+}
+
+impl<'a, N: WalkNode> Nodes<'a, N> {
+    pub(crate) fn new(roots: impl IntoIterator<Item = N>, breadth_first: bool) -> Self {
+        Self {
+            pending: roots.into_iter().map(|node| Visit { node, depth: 0, parent: None }).collect(),
+            breadth_first,
+            last: None,
+            kinds: NodeKinds::All,
+            filter: None,
+        }
+    }
+
+    /// Only return files. Directories are still walked through.
+    pub fn files(mut self) -> Self {
+        self.kinds = NodeKinds::Files;
+        self
+    }
+
+    /// Only return directories.
+    pub fn directories(mut self) -> Self {
+        self.kinds = NodeKinds::Directories;
+        self
+    }
+
+    /// Skip files that `filter` rejects, like the popups do.
+    pub fn with_filter(mut self, filter: &'a PathFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Don't go into the children of the directory returned last.
+    pub fn skip_children(&mut self) {
+        self.last = None;
+    }
+
+    fn queue_children(&mut self, visit: Visit<N>) {
+        let children = visit.node.children().map(|node| Visit { node, depth: visit.depth + 1, parent: Some(visit.node) });
+        if self.breadth_first {
+            self.pending.extend(children);
+        } else {
+            for child in children.rev() {
+                self.pending.push_front(child);
+            }
+        }
+    }
+}
+
+impl<N: WalkNode> Iterator for Nodes<'_, N> {
+    type Item = Visit<N>;
+
+    fn next(&mut self) -> Option<Visit<N>> {
+        loop {
+            if let Some(last) = self.last.take() {
+                self.queue_children(last);
+            }
+            let visit = self.pending.pop_front()?;
+
+            let is_dir = visit.node.is_dir();
+            if is_dir {
+                self.last = Some(visit);
+            }
+
+            let wanted = match self.kinds {
+                NodeKinds::All => true,
+                NodeKinds::Files => !is_dir,
+                NodeKinds::Directories => is_dir,
+            };
+            let visible = is_dir || self.filter.is_none_or(|f| visit.node.accepted_by(f));
+            if wanted && visible {
+                return Some(visit);
+            }
+        }
+    }
+}
+
+/// Call `visitor` on each node of `nodes` depth first, until it asks to stop.
+///
+/// Returns false if the walk was stopped early.
+fn visit_nodes<N: WalkNode>(mut nodes: Nodes<'_, N>, mut visitor: impl FnMut(Visit<N>) -> VisitControl) -> bool {
+    while let Some(visit) = nodes.next() {
+        match visitor(visit) {
+            VisitControl::Continue => {}
+            VisitControl::SkipChildren => nodes.skip_children(),
+            VisitControl::Stop => return false,
+        }
+    }
+    true
+}
+
+impl DirectoryNode {
+    /// This node and everything below it, depth first.
+    pub fn iter(&self) -> Nodes<'_, &DirectoryNode> {
+        Nodes::new([self], false)
+    }
+
+    /// This node and everything below it, a level at a time.
+    pub fn iter_breadth_first(&self) -> Nodes<'_, &DirectoryNode> {
+        Nodes::new([self], true)
+    }
+
+    /// Call `visitor` on this node and everything below it depth first, see `VisitControl`.
+    ///
+    /// Returns false if the visitor stopped the walk early.
+    pub fn visit<'a>(&'a self, visitor: impl FnMut(Visit<&'a DirectoryNode>) -> VisitControl) -> bool {
+        visit_nodes(self.iter(), visitor)
+    }
+}
+
+impl DirectoryComboBox {
+    /// Every node under the roots depth first, in the order the popups show them,
+    /// skipping files the filter rejects.
+    pub fn iter(&self) -> Nodes<'_, TreeNode<'_>> {
+        self.filtered(Nodes::new(self.root_nodes(), false))
+    }
+
+    /// Every node under the roots a level at a time, skipping files the filter rejects.
+    pub fn iter_breadth_first(&self) -> Nodes<'_, TreeNode<'_>> {
+        self.filtered(Nodes::new(self.root_nodes(), true))
+    }
+
+    /// Call `visitor` on every node under the roots depth first, skipping files the filter rejects.
+    ///
+    /// Returns false if the visitor stopped the walk early.
+    pub fn visit<'a>(&'a self, visitor: impl FnMut(Visit<TreeNode<'a>>) -> VisitControl) -> bool {
+        visit_nodes(self.iter(), visitor)
+    }
+
+    fn root_nodes(&self) -> impl Iterator<Item = TreeNode<'_>> {
+        let tree = self.tree();
+        tree.roots().iter().map(|&id| tree.node(id))
+    }
+
+    fn filtered<'a>(&'a self, nodes: Nodes<'a, TreeNode<'a>>) -> Nodes<'a, TreeNode<'a>> {
+        match &self.filter {
+            Some(filter) => nodes.with_filter(filter),
+            None => nodes,
+        }
+    }
+}
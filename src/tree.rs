@@ -0,0 +1,468 @@
+//! A compact copy of a directory tree, storing names instead of full paths.
+
+use std::{collections::VecDeque, ffi::{OsStr, OsString}, fmt, ops::Range, path::{Component, Path, PathBuf}};
+
+use dunce::canonicalize;
+
+use crate::{DirectoryNode, NodeMetadata};
+
+/// Identifies a node in a `DirectoryTree`.
+///
+/// Ids are only valid until the tree is changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    /// The file name, or the full path for a root.
+    name: OsString,
+    parent: Option<NodeId>,
+    /// Where the children of a directory are in `DirectoryTree::links`. `None` for files.
+    children: Option<Range<usize>>,
+    metadata: Option<NodeMetadata>,
+}
+
+/// A directory tree stored in a single arena, with full paths rebuilt from the names on demand.
+///
+/// Put it behind an `Arc` to share one scan between several combo boxes, see `DirectoryComboBox::new_from_tree`.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryTree {
+    entries: Vec<TreeEntry>,
+    /// The children of every directory, those of each directory next to each other.
+    links: Vec<NodeId>,
+    roots: Vec<NodeId>,
+    /// Entries and links no longer reachable from the roots, reclaimed once they pile up.
+    unused_entries: usize,
+    unused_links: usize,
+}
+
+impl DirectoryTree {
+    /// Scan `path` from disk, see `DirectoryNode::try_from_path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::from_paths(&[path])
+    }
+
+    /// Scan each of `paths` from disk as a root, see `DirectoryNode::try_from_path`.
+    ///
+    /// Returns `None` if any of them can't be scanned.
+    pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> Option<Self> {
+        let mut tree = Self::default();
+        for path in paths {
+            let path = path.as_ref();
+            if !path.exists() {
+                std::fs::create_dir_all(path).ok()?;
+            }
+            let root = tree.scan(path, None)?;
+            tree.roots.push(root);
+        }
+        Some(tree)
+    }
+
+    /// Scan `path` from disk with its entries as the roots, sorted by name, or as the only root if it is a file.
+    pub(crate) fn from_directory_entries(path: &Path) -> Option<Self> {
+        let mut tree = Self::from_path(path)?;
+        let top = tree.roots[0];
+        if !tree.is_dir(top) {
+            return Some(tree);
+        }
+
+        // `read_dir` order differs between file systems, and the roots are referred to by index
+        let mut roots = tree.children(top).to_vec();
+        roots.sort_by(|&a, &b| tree.name(a).cmp(tree.name(b)));
+        let dir = PathBuf::from(tree.name(top));
+        for &root in &roots {
+            let entry = &mut tree.entries[root.0];
+            entry.name = dir.join(&entry.name).into_os_string();
+            entry.parent = None;
+        }
+        tree.unused_entries += 1;
+        tree.unused_links += roots.len();
+        tree.roots = roots;
+        Some(tree)
+    }
+
+    pub fn from_nodes(roots: &[DirectoryNode]) -> Self {
+        let mut tree = Self::default();
+        for root in roots {
+            let id = tree.add_nodes(root, None);
+            tree.roots.push(id);
+        }
+        tree
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// The number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.unused_entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The node `id`, with its path and children.
+    pub fn node(&self, id: NodeId) -> TreeNode<'_> {
+        TreeNode { tree: self, id }
+    }
+
+    /// The file name of the node, or its full path for a root.
+    pub fn name(&self, id: NodeId) -> &OsStr {
+        &self.entries[id.0].name
+    }
+
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = vec![self.name(id)];
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            names.push(self.name(parent));
+            current = parent;
+        }
+        names.iter().rev().collect()
+    }
+
+    /// Whether `path` is the path of the node, checked without building it.
+    pub(crate) fn has_path(&self, id: NodeId, path: &Path) -> bool {
+        let mut path = path;
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            if path.file_name() != Some(self.name(current)) {
+                return false;
+            }
+            let Some(rest) = path.parent() else {
+                return false;
+            };
+            path = rest;
+            current = parent;
+        }
+        path == Path::new(self.name(current))
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    /// The children of a directory, or nothing for a file.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.entries[id.0].children {
+            Some(range) => &self.links[range.clone()],
+            None => &[],
+        }
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        self.entries[id.0].children.is_some()
+    }
+
+    pub fn metadata(&self, id: NodeId) -> Option<&NodeMetadata> {
+        self.entries[id.0].metadata.as_ref()
+    }
+
+    /// The node at `path`, found by following its components down from the root containing it.
+    pub fn find<P: AsRef<Path>>(&self, path: P) -> Option<NodeId> {
+        let path = path.as_ref();
+        self.roots.iter().find_map(|&root| {
+            let rest = path.strip_prefix(self.name(root)).ok()?;
+            rest.components().try_fold(root, |id, component| match component {
+                Component::Normal(name) => self.children(id).iter().copied().find(|&child| self.name(child) == name),
+                _ => None,
+            })
+        })
+    }
+
+    /// The node and everything below it, with full paths.
+    pub fn to_node(&self, id: NodeId) -> DirectoryNode {
+        self.build_node(id, self.path(id))
+    }
+
+    /// The whole tree as nodes.
+    pub fn to_nodes(&self) -> Vec<DirectoryNode> {
+        self.roots.iter().map(|&root| self.to_node(root)).collect()
+    }
+
+    fn build_node(&self, id: NodeId, path: PathBuf) -> DirectoryNode {
+        let metadata = self.metadata(id).cloned();
+        if !self.is_dir(id) {
+            return DirectoryNode::File(path, metadata);
+        }
+        let children = self.children(id)
+            .iter()
+            .map(|&child| self.build_node(child, path.join(self.name(child))))
+            .collect();
+        DirectoryNode::Directory(path, children, metadata)
+    }
+
+    fn push_entry(&mut self, name: OsString, parent: Option<NodeId>, is_dir: bool, metadata: Option<NodeMetadata>) -> NodeId {
+        let id = NodeId(self.entries.len());
+        let children = is_dir.then_some(0..0);
+        self.entries.push(TreeEntry { name, parent, children, metadata });
+        id
+    }
+
+    /// Store `children` as the children of `dir`, leaving the ones it had unused.
+    fn set_children(&mut self, dir: NodeId, children: impl IntoIterator<Item = NodeId>) {
+        let start = self.links.len();
+        self.links.extend(children);
+        let old = self.entries[dir.0].children.replace(start..self.links.len());
+        self.unused_links += old.map_or(0, |r| r.len());
+    }
+
+    /// Copy `node` and everything below it into the arena, breadth first, so the children of each
+    /// directory are next to each other.
+    fn add_nodes(&mut self, node: &DirectoryNode, parent: Option<NodeId>) -> NodeId {
+        let name = match parent {
+            Some(_) => node.path().file_name().unwrap_or(node.path().as_os_str()).to_os_string(),
+            None => node.path().as_os_str().to_os_string(),
+        };
+        let is_dir = matches!(node, DirectoryNode::Directory(..));
+        let top = self.push_entry(name, parent, is_dir, node.metadata().cloned());
+
+        let mut queue = VecDeque::from([(node, top)]);
+        while let Some((node, id)) = queue.pop_front() {
+            let DirectoryNode::Directory(_, children, _) = node else {
+                continue;
+            };
+            let ids: Vec<NodeId> = children.iter()
+                .map(|child| {
+                    let name = child.path().file_name().unwrap_or(child.path().as_os_str()).to_os_string();
+                    let is_dir = matches!(child, DirectoryNode::Directory(..));
+                    let child_id = self.push_entry(name, Some(id), is_dir, child.metadata().cloned());
+                    queue.push_back((child, child_id));
+                    child_id
+                })
+                .collect();
+            self.set_children(id, ids);
+        }
+        top
+    }
+
+    /// Scan `path` from disk into the arena below `parent`, or as a root with its canonical path as its name.
+    fn scan(&mut self, path: &Path, parent: Option<NodeId>) -> Option<NodeId> {
+        // Read before canonicalizing, which would resolve symbolic links
+        let metadata = NodeMetadata::read(path);
+        let real = canonicalize(path).ok()?;
+        let name = match parent {
+            Some(_) => path.file_name()?.to_os_string(),
+            None => real.clone().into_os_string(),
+        };
+
+        if real.is_file() {
+            return Some(self.push_entry(name, parent, false, Some(metadata)));
+        }
+        if !real.is_dir() {
+            return None;
+        }
+
+        let top = self.push_entry(name, parent, true, Some(metadata));
+        let mut queue = VecDeque::from([(top, real)]);
+        while let Some((dir, real)) = queue.pop_front() {
+            let entries = match std::fs::read_dir(&real) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::PermissionDenied
+                        && let Some(metadata) = &mut self.entries[dir.0].metadata
+                    {
+                        metadata.permission_denied = true;
+                    }
+                    continue;
+                }
+            };
+
+            let mut children = Vec::new();
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let child = entry.path();
+                // Follow symbolic links that stay below this directory, and ignore the rest
+                let child_real = if file_type.is_symlink() {
+                    match canonicalize(&child) {
+                        Ok(target) if target.starts_with(&real) && target != real => target,
+                        _ => continue,
+                    }
+                } else {
+                    child
+                };
+                let is_dir = if file_type.is_symlink() { child_real.is_dir() } else { file_type.is_dir() };
+                if !is_dir && !child_real.is_file() {
+                    continue;
+                }
+
+                let metadata = NodeMetadata::read(entry.path());
+                let id = self.push_entry(entry.file_name(), Some(dir), is_dir, Some(metadata));
+                children.push(id);
+                if is_dir {
+                    queue.push_back((id, child_real));
+                }
+            }
+            self.set_children(dir, children);
+        }
+        Some(top)
+    }
+
+    /// The number of nodes in the subtree of `id`, including itself, and of links between them.
+    fn subtree_size(&self, id: NodeId) -> (usize, usize) {
+        let children = self.children(id);
+        children.iter().fold((1, children.len()), |(entries, links), &child| {
+            let (child_entries, child_links) = self.subtree_size(child);
+            (entries + child_entries, links + child_links)
+        })
+    }
+
+    /// Change the list holding `id` and its siblings: the children of `parent`, or the roots.
+    fn update_siblings(&mut self, parent: Option<NodeId>, update: impl FnOnce(&mut Vec<NodeId>)) {
+        match parent {
+            Some(dir) => {
+                let mut children = self.children(dir).to_vec();
+                update(&mut children);
+                self.set_children(dir, children);
+            }
+            None => update(&mut self.roots),
+        }
+    }
+
+    /// Scan `path` from disk and add it as the last child of `parent`, or as the last root.
+    ///
+    /// Returns `None`, leaving the tree unchanged, if `path` can't be scanned.
+    pub(crate) fn insert_scanned(&mut self, parent: Option<NodeId>, path: &Path) -> Option<NodeId> {
+        let id = self.scan(path, parent)?;
+        self.update_siblings(parent, |siblings| siblings.push(id));
+        Some(id)
+    }
+
+    /// Scan the node from disk again, replacing it and everything below it.
+    ///
+    /// Returns false, leaving the tree unchanged, if its path no longer exists. Ids may change
+    /// either way, as the arena can be laid out again.
+    pub(crate) fn rescan(&mut self, id: NodeId) -> bool {
+        let path = self.path(id);
+        if !path.exists() {
+            return false;
+        }
+        let parent = self.parent(id);
+        let Some(new) = self.scan(&path, parent) else {
+            return false;
+        };
+        self.update_siblings(parent, |siblings| {
+            if let Some(slot) = siblings.iter_mut().find(|slot| **slot == id) {
+                *slot = new;
+            }
+        });
+        self.release(id);
+        true
+    }
+
+    /// Take the node and everything below it out of the tree. Ids may change, as the arena can be laid out again.
+    pub(crate) fn remove(&mut self, id: NodeId) {
+        let parent = self.parent(id);
+        self.update_siblings(parent, |siblings| siblings.retain(|&sibling| sibling != id));
+        self.release(id);
+    }
+
+    /// Give the node a new name, keeping its place and children.
+    pub(crate) fn rename(&mut self, id: NodeId, name: &OsStr) {
+        self.entries[id.0].name = name.to_os_string();
+    }
+
+    /// Count the subtree of `id` as unused, and lay the tree out again if most of the arena is unused.
+    fn release(&mut self, id: NodeId) {
+        let (entries, links) = self.subtree_size(id);
+        self.unused_entries += entries;
+        self.unused_links += links;
+        if self.unused_entries + self.unused_links > self.entries.len() {
+            self.compact();
+        }
+    }
+
+    /// Copy the nodes reachable from the roots into a new arena, breadth first.
+    fn compact(&mut self) {
+        let old = std::mem::take(self);
+        let mut queue = VecDeque::new();
+        for &root in &old.roots {
+            let id = self.copy_entry(&old, root, None);
+            self.roots.push(id);
+            queue.push_back((root, id));
+        }
+        while let Some((old_id, id)) = queue.pop_front() {
+            if !old.is_dir(old_id) {
+                continue;
+            }
+            let children: Vec<NodeId> = old.children(old_id)
+                .iter()
+                .map(|&child| {
+                    let child_id = self.copy_entry(&old, child, Some(id));
+                    queue.push_back((child, child_id));
+                    child_id
+                })
+                .collect();
+            self.set_children(id, children);
+        }
+    }
+
+    fn copy_entry(&mut self, from: &DirectoryTree, id: NodeId, parent: Option<NodeId>) -> NodeId {
+        let entry = &from.entries[id.0];
+        self.push_entry(entry.name.clone(), parent, entry.children.is_some(), entry.metadata.clone())
+    }
+}
+
+/// A node of a `DirectoryTree`, borrowed from it.
+#[derive(Clone, Copy)]
+pub struct TreeNode<'a> {
+    tree: &'a DirectoryTree,
+    id: NodeId,
+}
+
+impl<'a> TreeNode<'a> {
+    pub fn id(self) -> NodeId {
+        self.id
+    }
+
+    /// The file name of the node, or its full path for a root.
+    pub fn name(self) -> &'a OsStr {
+        self.tree.name(self.id)
+    }
+
+    pub fn path(self) -> PathBuf {
+        self.tree.path(self.id)
+    }
+
+    pub fn is_dir(self) -> bool {
+        self.tree.is_dir(self.id)
+    }
+
+    /// The metadata captured when this node was scanned, if it was scanned from disk.
+    pub fn metadata(self) -> Option<&'a NodeMetadata> {
+        self.tree.metadata(self.id)
+    }
+
+    pub fn parent(self) -> Option<TreeNode<'a>> {
+        self.tree.parent(self.id).map(|id| self.tree.node(id))
+    }
+
+    /// The children of a directory, or nothing for a file.
+    pub fn children(self) -> impl DoubleEndedIterator<Item = TreeNode<'a>> + ExactSizeIterator {
+        let tree = self.tree;
+        tree.children(self.id).iter().map(move |&id| tree.node(id))
+    }
+
+    /// The node and everything below it, with full paths.
+    pub fn to_node(self) -> DirectoryNode {
+        self.tree.to_node(self.id)
+    }
+}
+
+impl fmt::Debug for TreeNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreeNode").field("id", &self.id).field("path", &self.path()).finish()
+    }
+}
+
+impl PartialEq for TreeNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.tree, other.tree) && self.id == other.id
+    }
+}
+
+impl Eq for TreeNode<'_> {}
@@ -0,0 +1,83 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use egui_directory_combobox::DirectoryNode;
+
+/// A file node at `path`, made without the disk.
+pub fn file(path: &str) -> DirectoryNode {
+    DirectoryNode::File(PathBuf::from(path), None)
+}
+
+/// A directory node at `path`, made without the disk.
+pub fn dir(path: &str, children: Vec<DirectoryNode>) -> DirectoryNode {
+    DirectoryNode::Directory(PathBuf::from(path), children, None)
+}
+
+/// A scratch directory holding `files`, removed when dropped.
+pub struct TempTree(PathBuf);
+
+impl TempTree {
+    pub fn new(name: &str, files: &[&str]) -> Self {
+        let dir = std::env::temp_dir().join(format!("egui_directory_combobox_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            if file.ends_with('/') {
+                std::fs::create_dir_all(&path).unwrap();
+            } else {
+                std::fs::write(&path, "").unwrap();
+            }
+        }
+        Self(dunce::canonicalize(dir).unwrap())
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.0.join(relative)
+    }
+
+    /// The node of `relative`, with children in a fixed order rather than the order `read_dir` returns.
+    pub fn node(&self, relative: &str) -> DirectoryNode {
+        fn sorted(node: DirectoryNode) -> DirectoryNode {
+            match node {
+                DirectoryNode::Directory(p, children, metadata) => {
+                    let mut children: Vec<_> = children.into_iter().map(sorted).collect();
+                    children.sort_by(|a, b| a.path().cmp(b.path()));
+                    DirectoryNode::Directory(p, children, metadata)
+                }
+                file => file,
+            }
+        }
+        sorted(DirectoryNode::from_path(self.path(relative)))
+    }
+}
+
+/// The text `add_contents` draws, once the popups it opens have had a few frames to settle.
+pub fn rendered_text(mut add_contents: impl FnMut(&mut egui::Ui)) -> Vec<String> {
+    fn collect(shape: &egui::Shape, text: &mut Vec<String>) {
+        match shape {
+            egui::Shape::Text(shape) => text.push(shape.galley.text().to_string()),
+            egui::Shape::Vec(shapes) => shapes.iter().for_each(|s| collect(s, text)),
+            _ => {}
+        }
+    }
+
+    let ctx = egui::Context::default();
+    let mut text = Vec::new();
+    for _ in 0..3 {
+        let output = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| add_contents(ui));
+        });
+        text.clear();
+        output.shapes.iter().for_each(|s| collect(&s.shape, &mut text));
+    }
+    text
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::{path::PathBuf, sync::Once};
+
+use common::TempTree;
+use egui_directory_combobox::{DirectoryComboBox, SelectablePredicate};
+
+/// A trash directory of its own, so the tests don't fill the user's.
+fn trash_dir() -> PathBuf {
+    static INIT: Once = Once::new();
+    let dir = std::env::temp_dir().join(format!("egui_directory_combobox_trash_{}", std::process::id()));
+    INIT.call_once(|| {
+        let _ = std::fs::remove_dir_all(&dir);
+        // SAFETY: every test sets the variable through this before anything reads it
+        unsafe { std::env::set_var("XDG_DATA_HOME", &dir) };
+    });
+    dir.join("Trash")
+}
+
+#[test]
+fn created_entries_are_added_to_the_tree() {
+    let tree = TempTree::new("file_ops_create", &["d/a.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""));
+
+    let file = combobox.create_file(tree.path("d"), "b.txt").unwrap();
+    assert_eq!(file, tree.path("d/b.txt"));
+    assert!(file.is_file());
+    let directory = combobox.create_directory(tree.path("d"), "sub").unwrap();
+    assert!(directory.is_dir());
+    let nested = combobox.create_file(&directory, "c.txt").unwrap();
+
+    for path in [&file, &directory, &nested] {
+        assert!(combobox.contains_path(path), "{path:?}");
+    }
+    assert!(combobox.find_node(&directory).unwrap().is_dir());
+    assert_eq!(combobox.get_all_paths(), [tree.path("d/a.txt"), tree.path("d/b.txt"), tree.path("d/sub/c.txt")]);
+
+    // Existing names, names that aren't a single component and directories outside the roots are refused
+    assert!(combobox.create_file(tree.path("d"), "a.txt").is_err());
+    assert!(combobox.create_directory(tree.path("d"), "sub").is_err());
+    assert!(combobox.create_file(tree.path("d"), "../escaped.txt").is_err());
+    assert!(combobox.create_file(tree.path("d"), "").is_err());
+    assert!(combobox.create_file(tree.path(""), "outside.txt").is_err());
+    assert!(combobox.create_file(tree.path("d/a.txt"), "inside_a_file.txt").is_err());
+    assert!(!tree.path("escaped.txt").exists() && !tree.path("outside.txt").exists());
+}
+
+#[test]
+fn renaming_updates_the_tree_selection_and_favorites() {
+    let tree = TempTree::new("file_ops_rename", &["d/sub/f.txt", "d/g.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""))
+        .with_favorites(&[tree.path("d/sub"), tree.path("d/g.txt")]);
+    combobox.set_selection(Some(tree.path("d/sub/f.txt")));
+
+    let renamed = combobox.rename_path(tree.path("d/sub"), "renamed").unwrap();
+    assert_eq!(renamed, tree.path("d/renamed"));
+    assert!(renamed.join("f.txt").is_file() && !tree.path("d/sub").exists());
+
+    assert!(combobox.contains_path(tree.path("d/renamed/f.txt")));
+    assert!(!combobox.contains_path(tree.path("d/sub")));
+    assert_eq!(combobox.selected(), Some(tree.path("d/renamed/f.txt").as_path()));
+    assert_eq!(combobox.favorites(), [tree.path("d/renamed"), tree.path("d/g.txt")]);
+
+    // Renaming an entry outside the selection leaves it alone
+    combobox.rename_path(tree.path("d/g.txt"), "h.txt").unwrap();
+    assert_eq!(combobox.selected(), Some(tree.path("d/renamed/f.txt").as_path()));
+    assert_eq!(combobox.favorites(), [tree.path("d/renamed"), tree.path("d/h.txt")]);
+
+    assert_eq!(combobox.rename_path(tree.path("d/h.txt"), "h.txt").unwrap(), tree.path("d/h.txt"));
+    assert!(combobox.rename_path(tree.path("d/h.txt"), "renamed").is_err());
+    assert!(combobox.rename_path(tree.path("d/h.txt"), "a/b").is_err());
+    assert!(combobox.rename_path(tree.path("d"), "root").is_err());
+    assert!(tree.path("d/h.txt").is_file() && tree.path("d").is_dir());
+}
+
+#[test]
+fn an_opened_directory_that_cant_be_selected_stays_unselected() {
+    let tree = TempTree::new("file_ops_locked", &["d/a.txt", "d/b.txt", "d/locked/c.txt"]);
+    let selectable: SelectablePredicate = std::sync::Arc::new(|n| n.path().ends_with("locked").then(|| "Locked".to_string()));
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path("")).with_selectable(selectable);
+    combobox.set_selection(Some(tree.path("d/a.txt")));
+    combobox.open_directory(tree.path("d/locked"));
+
+    combobox.rename_path(tree.path("d/b.txt"), "renamed.txt").unwrap();
+    assert_eq!(combobox.selected(), Some(tree.path("d/a.txt").as_path()));
+    assert_eq!(combobox.selected_path(), Some(tree.path("d/locked").as_path()));
+}
+
+#[test]
+fn trashed_entries_leave_the_tree_and_the_selection_falls_back() {
+    let trash = trash_dir();
+    let tree = TempTree::new("file_ops_trash", &["d/sub/f.txt", "d/g.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""))
+        .with_favorites(&[tree.path("d/sub/f.txt"), tree.path("d/g.txt")]);
+    combobox.set_selection(Some(tree.path("d/sub/f.txt")));
+
+    combobox.trash_path(tree.path("d/sub")).unwrap();
+    assert!(!tree.path("d/sub").exists());
+    assert!(!combobox.contains_path(tree.path("d/sub")));
+    assert!(!combobox.contains_path(tree.path("d/sub/f.txt")));
+    assert_eq!(combobox.selected(), Some(tree.path("d").as_path()));
+    assert_eq!(combobox.favorites(), [tree.path("d/g.txt")]);
+
+    // The entry is in the trash, with a record of where it came from
+    let trashed = std::fs::read_dir(trash.join("files")).unwrap()
+        .map(|e| e.unwrap().path())
+        .find(|p| p.join("f.txt").is_file())
+        .unwrap();
+    let mut info = trash.join("info").join(trashed.file_name().unwrap());
+    info.as_mut_os_string().push(".trashinfo");
+    let info = std::fs::read_to_string(info).unwrap();
+    assert!(info.starts_with("[Trash Info]\n"));
+    assert!(info.contains(&format!("Path={}\n", tree.path("d/sub").display())), "{info}");
+
+    // Only files can be selected, so the selection is cleared rather than falling back
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path("")).select_files_only(true);
+    combobox.set_selection(Some(tree.path("d/g.txt")));
+    combobox.trash_path(tree.path("d/g.txt")).unwrap();
+    assert_eq!(combobox.selected(), None);
+    assert_eq!(combobox.selected_path(), Some(tree.path("d").as_path()));
+}
+
+#[test]
+fn roots_and_paths_outside_the_tree_are_not_trashed() {
+    // In case one of them is moved after all
+    trash_dir();
+    let tree = TempTree::new("file_ops_trash_roots", &["project/a.txt", "outside.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_paths(&[tree.path("project")]);
+
+    assert!(combobox.trash_path(tree.path("project")).is_err());
+    assert!(combobox.trash_path(tree.path("outside.txt")).is_err());
+    assert!(combobox.trash_path(tree.path("project/missing.txt")).is_err());
+    assert!(tree.path("project/a.txt").is_file() && tree.path("outside.txt").is_file());
+    assert!(combobox.contains_path(tree.path("project")));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::rendered_text;
+use egui_directory_combobox::{HierarchyComboBox, HierarchyNode};
+
+/// A category with items, or an item if it has no children.
+struct Category {
+    name: &'static str,
+    children: Option<Vec<Category>>,
+}
+
+impl HierarchyNode for Category {
+    type Key = &'static str;
+
+    fn key(&self) -> &'static str {
+        self.name
+    }
+
+    fn label(&self) -> String {
+        self.name.to_uppercase()
+    }
+
+    fn children(&self) -> Option<&[Category]> {
+        self.children.as_deref()
+    }
+}
+
+fn item(name: &'static str) -> Category {
+    Category { name, children: None }
+}
+
+fn category(name: &'static str, children: Vec<Category>) -> Category {
+    Category { name, children: Some(children) }
+}
+
+fn fonts() -> Vec<Category> {
+    vec![
+        category("serif", vec![item("times"), category("slab", vec![item("rockwell")])]),
+        category("sans", vec![item("helvetica")]),
+        category("empty", vec![]),
+    ]
+}
+
+#[test]
+fn entries_are_found_by_the_keys_leading_to_them() {
+    let combobox = HierarchyComboBox::new(fonts());
+
+    assert_eq!(combobox.node_at(&["serif", "slab", "rockwell"]).unwrap().name, "rockwell");
+    assert_eq!(combobox.node_at(&["sans"]).unwrap().name, "sans");
+    assert!(combobox.node_at(&["empty"]).unwrap().children().unwrap().is_empty());
+
+    assert!(combobox.node_at(&[]).is_none());
+    assert!(combobox.node_at(&["helvetica"]).is_none());
+    assert!(combobox.node_at(&["sans", "times"]).is_none());
+    assert!(combobox.node_at(&["serif", "times", "bold"]).is_none());
+}
+
+#[test]
+fn only_existing_entries_are_selected() {
+    let mut combobox = HierarchyComboBox::new(fonts());
+    combobox.set_selection(Some(&["serif", "slab", "rockwell"]));
+    assert_eq!(combobox.selected(), Some(&["serif", "slab", "rockwell"][..]));
+    assert_eq!(combobox.selected_node().unwrap().name, "rockwell");
+
+    // Keys leading nowhere leave the selection alone
+    combobox.set_selection(Some(&["serif", "rockwell"]));
+    assert_eq!(combobox.selected(), Some(&["serif", "slab", "rockwell"][..]));
+
+    combobox.set_selection(Some(&["serif", "slab"]));
+    assert_eq!(combobox.selected_node().unwrap().name, "slab");
+
+    combobox.set_selection(None);
+    assert!(combobox.selected().is_none() && combobox.selected_node().is_none());
+}
+
+#[test]
+fn branches_arent_selected_when_only_leaves_can_be() {
+    let mut combobox = HierarchyComboBox::new(fonts()).select_leaves_only(true);
+    combobox.set_selection(Some(&["sans", "helvetica"]));
+
+    combobox.set_selection(Some(&["serif"]));
+    combobox.set_selection(Some(&["empty"]));
+    assert_eq!(combobox.selected(), Some(&["sans", "helvetica"][..]));
+
+    combobox.set_selection(Some(&["serif", "times"]));
+    assert_eq!(combobox.selected_node().unwrap().name, "times");
+}
+
+#[test]
+fn the_button_shows_the_label_of_the_selection() {
+    let mut combobox = HierarchyComboBox::new(fonts());
+    let text = rendered_text(|ui| {
+        ui.add(&mut combobox);
+    });
+    assert_eq!(text, ["Select"]);
+
+    combobox.set_selection(Some(&["serif", "slab", "rockwell"]));
+    let text = rendered_text(|ui| {
+        ui.add(&mut combobox);
+    });
+    assert_eq!(text, ["ROCKWELL"]);
+}
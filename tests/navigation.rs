@@ -0,0 +1,157 @@
+mod common;
+
+use std::{path::Path, sync::Arc};
+
+use common::TempTree;
+use egui_directory_combobox::{DirectoryComboBox, DirectoryNode, NavigationMode, NavigationWrap, TreeNode};
+
+fn combobox(roots: Vec<DirectoryNode>) -> DirectoryComboBox {
+    DirectoryComboBox::new_from_nodes(roots)
+}
+
+fn selected_name(combobox: &DirectoryComboBox) -> Option<String> {
+    combobox.selected()
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().into_owned())
+}
+
+#[test]
+fn folder_navigation_wraps_to_first_visible_file() {
+    let tree = TempTree::new("folder_wrap", &["a.txt", "b.rs", "c.txt", "d.rs"]);
+    let mut combobox = combobox(vec![tree.node("")])
+        .select_files_only(true)
+        .with_filter(Arc::new(|p: &Path| p.extension().is_some_and(|e| e == "rs")));
+
+    combobox.set_selection(Some(tree.path("d.rs")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("b.rs"));
+
+    combobox.select_previous_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("d.rs"));
+}
+
+#[test]
+fn folder_navigation_includes_directories_unless_files_only() {
+    let tree = TempTree::new("folder_dirs", &["a.txt", "b/", "c.txt"]);
+    let mut combobox = combobox(vec![tree.node("")]);
+
+    combobox.set_selection(Some(tree.path("a.txt")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("b"));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+
+    let mut combobox = combobox.select_files_only(true);
+    combobox.set_selection(Some(tree.path("a.txt")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+}
+
+#[test]
+fn navigation_skips_unselectable_entries() {
+    let tree = TempTree::new("unselectable", &["a.txt", "b.txt", "c.txt"]);
+    let mut combobox = combobox(vec![tree.node("")])
+        .with_selectable(Arc::new(|n: TreeNode<'_>| {
+            n.path().ends_with("b.txt").then(|| "Locked".to_string())
+        }));
+
+    combobox.set_selection(Some(tree.path("a.txt")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+}
+
+#[test]
+fn depth_first_navigation_crosses_directories() {
+    let tree = TempTree::new("depth_first", &["a/1.txt", "a/2.txt", "b/3.txt", "c.txt"]);
+    let mut combobox = combobox(vec![tree.node("")])
+        .select_files_only(true)
+        .with_navigation(NavigationMode::DepthFirst(NavigationWrap::Stop));
+
+    combobox.set_selection(Some(tree.path("a/2.txt")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("3.txt"));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+
+    // Stops at the end
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+
+    combobox.select_previous_file();
+    combobox.select_previous_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("2.txt"));
+}
+
+#[test]
+fn depth_first_navigation_honors_filter_when_wrapping() {
+    let tree = TempTree::new("depth_first_filter", &["a/1.txt", "a/2.rs", "b/3.rs", "b/4.txt"]);
+    let mut combobox = combobox(vec![tree.node("")])
+        .select_files_only(true)
+        .with_filter(Arc::new(|p: &Path| p.extension().is_some_and(|e| e == "rs")))
+        .with_navigation(NavigationMode::DepthFirst(NavigationWrap::WithinRoot));
+
+    combobox.set_selection(Some(tree.path("b/3.rs")));
+    combobox.select_next_file();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("2.rs"));
+}
+
+#[test]
+fn depth_first_navigation_wraps_within_or_across_roots() {
+    let first = TempTree::new("roots_first", &["1.txt", "2.txt"]);
+    let second = TempTree::new("roots_second", &["3.txt"]);
+    let roots = vec![first.node(""), second.node("")];
+
+    let mut within = combobox(roots.clone())
+        .select_files_only(true)
+        .with_navigation(NavigationMode::DepthFirst(NavigationWrap::WithinRoot));
+    within.set_selection(Some(first.path("2.txt")));
+    within.select_next_file();
+    assert_eq!(selected_name(&within).as_deref(), Some("1.txt"));
+
+    let mut across = combobox(roots)
+        .select_files_only(true)
+        .with_navigation(NavigationMode::DepthFirst(NavigationWrap::AcrossRoots));
+    across.set_selection(Some(first.path("2.txt")));
+    across.select_next_file();
+    assert_eq!(selected_name(&across).as_deref(), Some("3.txt"));
+    across.select_next_file();
+    assert_eq!(selected_name(&across).as_deref(), Some("1.txt"));
+}
+
+#[test]
+fn parent_child_and_sibling_navigation() {
+    let tree = TempTree::new("parent_child", &["a/1.txt", "a/2.txt", "b/3.txt", "c.txt"]);
+    let mut combobox = combobox(vec![tree.node("")]);
+
+    combobox.set_selection(Some(tree.path("a/2.txt")));
+    combobox.select_parent();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("a"));
+
+    combobox.select_next_sibling();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("b"));
+    combobox.select_first_child();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("3.txt"));
+
+    combobox.select_parent();
+    combobox.select_next_sibling();
+    combobox.select_next_sibling();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("c.txt"));
+    combobox.select_previous_sibling();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("b"));
+}
+
+#[test]
+fn directories_are_only_opened_in_files_only_mode() {
+    let tree = TempTree::new("files_only_parent", &["a/1.txt", "b/2.txt"]);
+    let mut combobox = combobox(vec![tree.node("")]).select_files_only(true);
+
+    combobox.set_selection(Some(tree.path("a/1.txt")));
+    combobox.select_parent();
+    combobox.select_next_sibling();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("1.txt"));
+    combobox.select_first_child();
+    assert_eq!(selected_name(&combobox).as_deref(), Some("2.txt"));
+
+    combobox.open_directory(tree.path("a"));
+    assert_eq!(selected_name(&combobox).as_deref(), Some("2.txt"));
+}
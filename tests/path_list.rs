@@ -0,0 +1,113 @@
+mod common;
+
+use common::{TempTree, dir, file, rendered_text};
+use egui_directory_combobox::{DirectoryComboBox, DirectoryNode};
+
+#[test]
+fn dots_are_resolved_without_the_disk() {
+    let nodes = DirectoryNode::from_path_list(&[
+        "./src/main.rs",
+        "src/lib/../lib.rs",
+        "src/./bin/tool.rs",
+        "../up.txt",
+        "a/../../above.txt",
+        "/abs/x/../y.txt",
+        "/../z.txt",
+        ".",
+    ]);
+    assert_eq!(nodes, [
+        dir("src", vec![
+            file("src/main.rs"),
+            file("src/lib.rs"),
+            dir("src/bin", vec![file("src/bin/tool.rs")]),
+        ]),
+        dir("..", vec![file("../up.txt"), file("../above.txt")]),
+        dir("/abs", vec![file("/abs/y.txt")]),
+        // Nothing is above the root directory
+        file("/z.txt"),
+    ]);
+}
+
+#[test]
+fn trailing_separators_make_directories() {
+    let nodes = DirectoryNode::from_path_list(&["docs/", "empty/nested/", "readme", "docs/guide.md"]);
+    assert_eq!(nodes, [
+        dir("docs", vec![file("docs/guide.md")]),
+        dir("empty", vec![dir("empty/nested", vec![])]),
+        file("readme"),
+    ]);
+}
+
+#[test]
+fn entries_keep_the_order_they_first_appear_in() {
+    let nodes = DirectoryNode::from_path_list(&[
+        "b/2.txt",
+        "a/1.txt",
+        "b/1.txt",
+        "b/2.txt",
+        "a/",
+        "a/1.txt",
+        "b/sub/x",
+        "b/2.txt",
+    ]);
+    assert_eq!(nodes, [
+        dir("b", vec![file("b/2.txt"), file("b/1.txt"), dir("b/sub", vec![file("b/sub/x")])]),
+        dir("a", vec![file("a/1.txt")]),
+    ]);
+    assert!(nodes.iter().flat_map(|n| n.iter()).all(|v| v.node.metadata().is_none()));
+
+    // A file becomes a directory once something is listed inside it
+    let nodes = DirectoryNode::from_path_list(&["a", "a/b"]);
+    assert_eq!(nodes, [dir("a", vec![file("a/b")])]);
+}
+
+#[test]
+fn listed_entries_are_not_refreshed_or_changed_on_disk() {
+    let tree = TempTree::new("path_list_disk", &["a/1.txt", "a/2.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path_list(&[tree.path("a/1.txt")]);
+
+    // A rescan would pick up 2.txt
+    combobox.refresh_path(tree.path("a"));
+    combobox.refresh_path(tree.path("a/1.txt"));
+    assert_eq!(combobox.get_all_paths(), [tree.path("a/1.txt")]);
+
+    // Missing from the disk doesn't remove them either
+    let mut missing = DirectoryComboBox::new_from_path_list(&["not/on/disk.txt"]);
+    missing.refresh_path("not/on");
+    assert!(missing.contains_path("not/on/disk.txt"));
+
+    assert!(combobox.create_file(tree.path("a"), "3.txt").is_err());
+    assert!(combobox.create_directory(tree.path("a"), "new").is_err());
+    assert!(combobox.rename_path(tree.path("a/1.txt"), "renamed.txt").is_err());
+    assert!(combobox.trash_path(tree.path("a/1.txt")).is_err());
+    assert!(!tree.path("a/3.txt").exists() && !tree.path("a/new").exists());
+    assert!(tree.path("a/1.txt").exists());
+
+    let mut node = file("not/on/disk.txt");
+    assert!(!node.refresh());
+    assert_eq!(node, file("not/on/disk.txt"));
+}
+
+#[test]
+fn roots_without_a_file_name_are_shown_whole() {
+    let mut combobox = DirectoryComboBox::new_from_nodes(
+        DirectoryNode::from_path_list(&["../assets/a.png"]).into_iter().chain([dir("/", vec![file("/b.txt")])]).collect(),
+    );
+    assert!(combobox.contains_path("../assets/a.png") && combobox.contains_path("/b.txt"));
+
+    combobox.open();
+    let text = rendered_text(|ui| {
+        ui.add(&mut combobox);
+    });
+    assert!(text.iter().any(|t| t == "..") && text.iter().any(|t| t == "/"), "{text:?}");
+
+    // Selected, a root's whole path is shown on the button too
+    combobox.set_selection(Some(".."));
+    assert_eq!(combobox.selected(), Some(std::path::Path::new("..")));
+    combobox.open();
+    let text = rendered_text(|ui| {
+        ui.add(&mut combobox);
+    });
+    assert!(text.iter().filter(|t| *t == "..").count() >= 2, "{text:?}");
+    assert!(text.iter().any(|t| t == "assets"), "{text:?}");
+}
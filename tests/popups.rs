@@ -0,0 +1,34 @@
+mod common;
+
+use std::{path::Path, sync::{Arc, Mutex}};
+
+use common::{TempTree, rendered_text};
+use egui_directory_combobox::{DirectoryComboBox, PathFilter};
+
+#[test]
+fn refilter_picks_up_a_changed_filter() {
+    let tree = TempTree::new("popups_refilter", &["a.txt", "b.rs"]);
+    let query = Arc::new(Mutex::new("txt".to_string()));
+    let filter_query = query.clone();
+    let filter: PathFilter = Arc::new(move |p: &Path| p.to_string_lossy().ends_with(&*filter_query.lock().unwrap()));
+    let mut combobox = DirectoryComboBox::new_from_paths(&[tree.path("")]).with_filter(filter);
+    combobox.set_selection(Some(tree.path("")));
+
+    let shown = |combobox: &mut DirectoryComboBox| {
+        combobox.open();
+        rendered_text(|ui| {
+            ui.add(&mut *combobox);
+        })
+    };
+    let text = shown(&mut combobox);
+    assert!(text.iter().any(|t| t == "a.txt") && !text.iter().any(|t| t == "b.rs"), "{text:?}");
+
+    // The popups keep the entries they checked until asked to check again
+    *query.lock().unwrap() = "rs".to_string();
+    assert_eq!(combobox.get_all_paths(), [tree.path("b.rs")]);
+    assert!(shown(&mut combobox).iter().any(|t| t == "a.txt"));
+
+    combobox.refilter();
+    let text = shown(&mut combobox);
+    assert!(text.iter().any(|t| t == "b.rs") && !text.iter().any(|t| t == "a.txt"), "{text:?}");
+}
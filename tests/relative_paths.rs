@@ -0,0 +1,45 @@
+mod common;
+
+use common::TempTree;
+use egui_directory_combobox::{DirectoryComboBox, RootRelativePath};
+
+#[test]
+fn roots_from_a_directory_are_sorted_by_name() {
+    let tree = TempTree::new("sorted_roots", &["c/1.txt", "a/2.txt", "b.txt"]);
+    let combobox = DirectoryComboBox::new_from_path(tree.path(""));
+
+    let names: Vec<_> = combobox.tree().roots().iter()
+        .map(|&r| combobox.tree().path(r).file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["a", "b.txt", "c"]);
+}
+
+#[test]
+fn relative_selection_round_trips() {
+    let tree = TempTree::new("relative_round_trip", &["a/1.txt", "c/sub/image.png"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""));
+
+    combobox.set_selection(Some(tree.path("c/sub/image.png")));
+    let relative = combobox.selected_relative().unwrap();
+    assert_eq!(relative, RootRelativePath { root: 1, path: "sub/image.png".to_string() });
+
+    // A fresh scan, as on another machine, resolves the same reference
+    let mut other = DirectoryComboBox::new_from_path(tree.path(""));
+    other.set_selection_relative(relative.root, &relative.path);
+    assert_eq!(other.selected(), Some(tree.path("c/sub/image.png").as_path()));
+    assert_eq!(other.selected_relative(), Some(relative));
+}
+
+#[test]
+fn relative_paths_cannot_escape_their_root() {
+    let tree = TempTree::new("relative_escape", &["a/1.txt", "b/2.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""));
+
+    combobox.set_selection_relative(0, "../b/2.txt");
+    assert_eq!(combobox.selected(), None);
+
+    // The root itself is the empty path
+    combobox.set_selection_relative(1, "");
+    assert_eq!(combobox.selected(), Some(tree.path("b").as_path()));
+    assert_eq!(combobox.selected_relative().unwrap().path, "");
+}
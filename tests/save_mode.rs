@@ -0,0 +1,88 @@
+mod common;
+
+use common::TempTree;
+use egui_directory_combobox::DirectoryComboBox;
+
+fn key(key: egui::Key) -> egui::Event {
+    egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers: Default::default() }
+}
+
+/// Open the popup, focus the file name field and press Enter in it.
+fn submit(combobox: &mut DirectoryComboBox, ctx: &egui::Context) {
+    combobox.open();
+    // The combo box, the popup and then the file name field take the focus in turn
+    let mut frames = vec![vec![]];
+    frames.extend([vec![key(egui::Key::Tab)], vec![key(egui::Key::Tab)], vec![key(egui::Key::Tab)]]);
+    frames.extend([vec![key(egui::Key::Enter)], vec![]]);
+    for events in frames {
+        let _ = ctx.run(egui::RawInput { events, ..Default::default() }, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.add(&mut *combobox));
+        });
+    }
+}
+
+#[test]
+fn new_files_can_be_selected_in_existing_directories() {
+    let tree = TempTree::new("save_selection", &["d/a.txt", "d/sub/"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path("")).save_mode(true);
+
+    combobox.set_selection(Some(tree.path("d/new.txt")));
+    assert_eq!(combobox.selected(), Some(tree.path("d/new.txt").as_path()));
+    assert_eq!(combobox.selected_path(), Some(tree.path("d").as_path()));
+
+    // Opening a directory keeps the file name
+    combobox.set_selection(Some(tree.path("d/sub")));
+    assert_eq!(combobox.selected(), Some(tree.path("d/new.txt").as_path()));
+    assert_eq!(combobox.selected_path(), Some(tree.path("d/sub").as_path()));
+
+    // The directory of the file has to exist
+    combobox.set_selection(Some(tree.path("missing/new.txt")));
+    assert_eq!(combobox.selected(), Some(tree.path("d/new.txt").as_path()));
+
+    // Without save mode, only existing paths can be selected
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""));
+    combobox.set_selection(Some(tree.path("d/new.txt")));
+    assert_eq!(combobox.selected(), None);
+}
+
+#[test]
+fn the_extension_is_added_unless_the_name_has_it() {
+    let tree = TempTree::new("save_extension", &["d/a.txt"]);
+    let ctx = egui::Context::default();
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""))
+        .save_mode(true)
+        .with_save_extension(".txt");
+
+    combobox.set_selection(Some(tree.path("d/report")));
+    submit(&mut combobox, &ctx);
+    assert_eq!(combobox.selected(), Some(tree.path("d/report.txt").as_path()));
+    assert!(!combobox.is_open(&ctx));
+
+    combobox.set_selection(Some(tree.path("d/Notes.TXT")));
+    submit(&mut combobox, &ctx);
+    assert_eq!(combobox.selected(), Some(tree.path("d/Notes.TXT").as_path()));
+
+    // Saving over an existing file asks first, keeping the popup open
+    combobox.set_selection(Some(tree.path("d/a")));
+    submit(&mut combobox, &ctx);
+    assert!(combobox.is_open(&ctx));
+    assert_eq!(combobox.selected(), Some(tree.path("d/a").as_path()));
+}
+
+#[test]
+fn changing_other_entries_keeps_the_file_to_save() {
+    let tree = TempTree::new("save_remap", &["d/s/other.txt", "d/t.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path("")).save_mode(true);
+    combobox.set_selection(Some(tree.path("d/s/new.txt")));
+
+    combobox.rename_path(tree.path("d/s/other.txt"), "renamed.txt").unwrap();
+    assert_eq!(combobox.selected(), Some(tree.path("d/s/new.txt").as_path()));
+    combobox.rename_path(tree.path("d/t.txt"), "u.txt").unwrap();
+    assert_eq!(combobox.selected(), Some(tree.path("d/s/new.txt").as_path()));
+    assert_eq!(combobox.selected_path(), Some(tree.path("d/s").as_path()));
+
+    // Renaming the directory it is in moves it along
+    combobox.rename_path(tree.path("d/s"), "moved").unwrap();
+    assert_eq!(combobox.selected(), Some(tree.path("d/moved/new.txt").as_path()));
+    assert_eq!(combobox.selected_path(), Some(tree.path("d/moved").as_path()));
+}
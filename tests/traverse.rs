@@ -0,0 +1,160 @@
+mod common;
+
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use common::{dir, file};
+use egui_directory_combobox::{DirectoryComboBox, DirectoryNode, PathFilter, VisitControl};
+
+/// `/r` holding `a/a1.txt`, `a/a2/deep.txt`, `b.txt` and an empty `c`.
+fn root() -> DirectoryNode {
+    dir("/r", vec![
+        dir("/r/a", vec![
+            file("/r/a/a1.txt"),
+            dir("/r/a/a2", vec![file("/r/a/a2/deep.txt")]),
+        ]),
+        file("/r/b.txt"),
+        dir("/r/c", vec![]),
+    ])
+}
+
+/// `root` followed by a second root, the file `/s.txt`.
+fn combobox() -> DirectoryComboBox {
+    DirectoryComboBox::new_from_nodes(vec![root(), file("/s.txt")])
+}
+
+fn name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+#[test]
+fn depth_first_visits_directories_before_their_children() {
+    let root = root();
+    let visits: Vec<_> = root.iter().map(|v| (name(v.node.path()), v.depth)).collect();
+    assert_eq!(visits, [
+        ("r".to_string(), 0),
+        ("a".to_string(), 1),
+        ("a1.txt".to_string(), 2),
+        ("a2".to_string(), 2),
+        ("deep.txt".to_string(), 3),
+        ("b.txt".to_string(), 1),
+        ("c".to_string(), 1),
+    ]);
+}
+
+#[test]
+fn both_orders_walk_every_root() {
+    let combobox = combobox();
+    let names: Vec<_> = combobox.iter_breadth_first().map(|v| name(&v.node.path())).collect();
+    assert_eq!(names, ["r", "s.txt", "a", "b.txt", "c", "a1.txt", "a2", "deep.txt"]);
+
+    let names: Vec<_> = combobox.iter().map(|v| name(&v.node.path())).collect();
+    assert_eq!(names, ["r", "a", "a1.txt", "a2", "deep.txt", "b.txt", "c", "s.txt"]);
+}
+
+#[test]
+fn visits_know_their_parent_and_depth() {
+    let combobox = combobox();
+    for visit in combobox.iter().chain(combobox.iter_breadth_first()) {
+        let path = visit.node.path();
+        assert_eq!(visit.parent.map(|p| p.path()).as_deref(), visit.node.parent().map(|p| p.path()).as_deref());
+        assert_eq!(visit.parent.map(|p| p.path()).as_deref(), path.parent().filter(|p| *p != Path::new("/")));
+        assert_eq!(visit.depth, path.components().count() - 2, "{path:?}");
+    }
+
+    // Walks started below the roots count from where they started
+    let root = root();
+    let a = root.iter().find(|v| v.node.path() == Path::new("/r/a")).unwrap().node;
+    let visits: Vec<_> = a.iter()
+        .map(|v| (name(v.node.path()), v.depth, v.parent.map(|p| name(p.path()))))
+        .collect();
+    assert_eq!(visits, [
+        ("a".to_string(), 0, None),
+        ("a1.txt".to_string(), 1, Some("a".to_string())),
+        ("a2".to_string(), 1, Some("a".to_string())),
+        ("deep.txt".to_string(), 2, Some("a2".to_string())),
+    ]);
+}
+
+#[test]
+fn skip_children_in_both_orders() {
+    let root = root();
+    let mut names = Vec::new();
+    let finished = root.visit(|v| {
+        names.push(name(v.node.path()));
+        if v.node.path().ends_with("a") { VisitControl::SkipChildren } else { VisitControl::Continue }
+    });
+    assert!(finished);
+    assert_eq!(names, ["r", "a", "b.txt", "c"]);
+
+    let combobox = combobox();
+    let mut nodes = combobox.iter_breadth_first();
+    let mut names = Vec::new();
+    while let Some(visit) = nodes.next() {
+        names.push(name(&visit.node.path()));
+        if visit.node.path().ends_with("a") {
+            nodes.skip_children();
+        }
+    }
+    assert_eq!(names, ["r", "s.txt", "a", "b.txt", "c"]);
+
+    // Skipping after a file leaves the directory before it alone
+    let mut nodes = combobox.iter();
+    let mut names = Vec::new();
+    while let Some(visit) = nodes.next() {
+        names.push(name(&visit.node.path()));
+        if visit.node.path().ends_with("a1.txt") {
+            nodes.skip_children();
+        }
+    }
+    assert_eq!(names, ["r", "a", "a1.txt", "a2", "deep.txt", "b.txt", "c", "s.txt"]);
+}
+
+#[test]
+fn stop_ends_the_walk() {
+    let combobox = combobox();
+    let mut names = Vec::new();
+    let finished = combobox.visit(|v| {
+        names.push(name(&v.node.path()));
+        if v.node.path().ends_with("a2") { VisitControl::Stop } else { VisitControl::Continue }
+    });
+    assert!(!finished);
+    assert_eq!(names, ["r", "a", "a1.txt", "a2"]);
+
+    assert!(combobox.visit(|_| VisitControl::Continue));
+}
+
+#[test]
+fn files_and_directories_are_walked_through_each_other() {
+    let combobox = combobox();
+    let files: Vec<_> = combobox.iter().files().map(|v| name(&v.node.path())).collect();
+    assert_eq!(files, ["a1.txt", "deep.txt", "b.txt", "s.txt"]);
+    let files: Vec<_> = combobox.iter_breadth_first().files().map(|v| name(&v.node.path())).collect();
+    assert_eq!(files, ["s.txt", "b.txt", "a1.txt", "deep.txt"]);
+
+    let directories: Vec<_> = combobox.iter_breadth_first().directories().map(|v| name(&v.node.path())).collect();
+    assert_eq!(directories, ["r", "a", "c", "a2"]);
+
+    // Skipping a directory keeps the directories below it out too
+    let mut nodes = combobox.iter().directories();
+    let mut names = Vec::new();
+    while let Some(visit) = nodes.next() {
+        names.push(name(&visit.node.path()));
+        if visit.node.path().ends_with("a") {
+            nodes.skip_children();
+        }
+    }
+    assert_eq!(names, ["r", "a", "c"]);
+}
+
+#[test]
+fn filter_hides_files_but_not_directories() {
+    let filter: PathFilter = Arc::new(|p: &Path| !p.ends_with("b.txt") && !p.ends_with("deep.txt"));
+    let combobox = combobox().with_filter(filter.clone());
+    let names: Vec<_> = combobox.iter().map(|v| name(&v.node.path())).collect();
+    assert_eq!(names, ["r", "a", "a1.txt", "a2", "c", "s.txt"]);
+    assert_eq!(combobox.get_all_paths(), [PathBuf::from("/r/a/a1.txt"), PathBuf::from("/s.txt")]);
+
+    let root = root();
+    let files: Vec<_> = root.iter().with_filter(&filter).files().map(|v| name(v.node.path())).collect();
+    assert_eq!(files, ["a1.txt"]);
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{TempTree, dir, file};
+use egui_directory_combobox::{DirectoryComboBox, DirectoryNode, DirectoryTree, NodeId};
+
+/// Two roots, with directories at several depths so their children are stored apart.
+fn roots() -> Vec<DirectoryNode> {
+    vec![
+        dir("/r", vec![
+            dir("/r/a", vec![
+                file("/r/a/a1.txt"),
+                dir("/r/a/a2", vec![file("/r/a/a2/deep.txt")]),
+                file("/r/a/a3.txt"),
+            ]),
+            file("/r/b.txt"),
+            dir("/r/c", vec![]),
+            dir("/r/d", vec![file("/r/d/d1.txt")]),
+        ]),
+        dir("/s", vec![file("/s/s1.txt"), dir("/s/ab", vec![])]),
+    ]
+}
+
+fn names(tree: &DirectoryTree, ids: &[NodeId]) -> Vec<String> {
+    ids.iter().map(|&id| tree.name(id).to_string_lossy().into_owned()).collect()
+}
+
+#[test]
+fn from_nodes_keeps_each_directory_with_its_children() {
+    let roots = roots();
+    let tree = DirectoryTree::from_nodes(&roots);
+    assert_eq!(tree.len(), 13);
+    assert_eq!(names(&tree, tree.roots()), ["/r", "/s"]);
+
+    for expected in roots.iter().flat_map(|r| r.iter()).map(|v| v.node) {
+        let id = tree.find(expected.path()).unwrap();
+        assert_eq!(tree.path(id), expected.path());
+        assert_eq!(tree.is_dir(id), matches!(expected, DirectoryNode::Directory(..)));
+
+        let children: Vec<&Path> = tree.children(id).iter().map(|&c| tree.name(c).as_ref()).collect();
+        let expected_children: Vec<&Path> = match expected {
+            DirectoryNode::Directory(_, children, _) => children.iter().map(|c| Path::new(c.path().file_name().unwrap())).collect(),
+            DirectoryNode::File(..) => Vec::new(),
+        };
+        assert_eq!(children, expected_children, "{:?}", expected.path());
+        for &child in tree.children(id) {
+            assert_eq!(tree.parent(child), Some(id));
+        }
+    }
+
+    assert_eq!(tree.to_nodes(), roots);
+}
+
+#[test]
+fn empty_directories_and_files_have_no_children() {
+    let tree = DirectoryTree::from_nodes(&roots());
+    let empty = tree.find("/r/c").unwrap();
+    assert!(tree.is_dir(empty));
+    assert!(tree.children(empty).is_empty());
+
+    let file = tree.find("/r/b.txt").unwrap();
+    assert!(!tree.is_dir(file));
+    assert!(tree.children(file).is_empty());
+
+    assert_eq!(tree.find("/r/b.txt/x"), None);
+    assert_eq!(tree.find("/r/missing"), None);
+    assert!(DirectoryTree::from_nodes(&[]).is_empty());
+}
+
+#[test]
+fn find_node_looks_up_every_path() {
+    let roots = roots();
+    let combobox = DirectoryComboBox::new_from_nodes(roots.clone());
+
+    for root in &roots {
+        for visit in root.iter() {
+            let node = combobox.find_node(visit.node.path()).unwrap();
+            assert_eq!(node.path(), visit.node.path());
+            assert!(combobox.contains_path(visit.node.path()));
+        }
+    }
+
+    // Paths sharing a prefix with a node aren't mistaken for it
+    for missing in ["/r/a/a", "/r/a2", "/s/a", "/r/b", "/", "", "r", "/r/a/../b.txt"] {
+        assert!(combobox.find_node(missing).is_none(), "{missing}");
+        assert!(!combobox.contains_path(missing), "{missing}");
+    }
+}
+
+#[test]
+fn find_node_follows_changes_to_the_tree() {
+    let mut combobox = DirectoryComboBox::new_from_nodes(roots());
+    assert!(combobox.contains_path("/r/a/a2/deep.txt"));
+
+    combobox.set_roots(&[dir("/t", vec![file("/t/a2"), file("/t/deep.txt")])]);
+    assert!(!combobox.contains_path("/r/a/a2/deep.txt"));
+    assert!(!combobox.contains_path("/r"));
+    assert_eq!(combobox.find_node("/t/deep.txt").map(|n| n.path()), Some(PathBuf::from("/t/deep.txt")));
+
+    // Clones share the tree until one of them replaces it
+    let clone = combobox.clone();
+    combobox.set_tree(DirectoryTree::from_nodes(&roots()));
+    assert!(combobox.contains_path("/r/a/a1.txt"));
+    assert!(!clone.contains_path("/r/a/a1.txt"));
+    assert!(clone.contains_path("/t/a2"));
+}
+
+#[test]
+fn a_path_under_two_roots_is_found_under_the_first_containing_it() {
+    let combobox = DirectoryComboBox::new_from_nodes(vec![
+        dir("/r", vec![file("/r/x.txt")]),
+        dir("/r", vec![file("/r/x.txt"), file("/r/y.txt")]),
+    ]);
+    let tree = combobox.tree();
+
+    let x = combobox.find_node("/r/x.txt").unwrap();
+    assert_eq!(x.parent().map(|p| p.id()), Some(tree.roots()[0]));
+    let y = combobox.find_node("/r/y.txt").unwrap();
+    assert_eq!(y.parent().map(|p| p.id()), Some(tree.roots()[1]));
+}
+
+#[test]
+fn refreshing_again_and_again_keeps_every_node() {
+    let tree = TempTree::new("tree_refresh", &["a/b/c/d.txt", "a/b/e.txt", "a/f.txt", "g/h.txt"]);
+    let mut combobox = DirectoryComboBox::new_from_path(tree.path(""));
+    let paths = combobox.iter().map(|v| v.node.path()).collect::<Vec<_>>();
+
+    // Every refresh leaves the old nodes behind, until the tree is laid out again
+    for i in 0..20 {
+        let refreshed = ["a", "a/b", "a/b/c/d.txt", "g"][i % 4];
+        combobox.refresh_path(tree.path(refreshed));
+        assert_eq!(combobox.tree().len(), paths.len());
+        for path in &paths {
+            let node = combobox.find_node(path).unwrap();
+            assert_eq!(&node.path(), path);
+        }
+    }
+    assert_eq!(combobox.iter().map(|v| v.node.path()).collect::<Vec<_>>(), paths);
+}